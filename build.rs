@@ -0,0 +1,19 @@
+fn main () {
+    // Generates the C header of the `ffi` module into `OUT_DIR`. The copy in `include/` is
+    // checked against it by the `ffi` tests.
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out = std::env::var("OUT_DIR").unwrap();
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir)).expect("invalid cbindgen.toml");
+        // Only the `ffi` module is parsed, so the rest of the crate's public items stay out of the header.
+        cbindgen::Builder::new()
+            .with_src(format!("{}/src/ffi.rs", dir))
+            .with_config(config)
+            .generate()
+            .expect("failed to generate C header")
+            .write_to_file(format!("{}/tetron.h", out));
+    }
+}
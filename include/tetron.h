@@ -0,0 +1,213 @@
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#ifndef TETRON_H
+#define TETRON_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Maximum amount of keys in a `TetronResult`, as many as a move records.
+ */
+#define TETRON_MAX_KEYS 15
+
+/**
+ * Key codes, as listed in `TetronResult.keys`.
+ */
+enum TetronKey
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+  TETRON_KEY_LEFT = 0,
+  TETRON_KEY_RIGHT = 1,
+  TETRON_KEY_DAS_LEFT = 2,
+  TETRON_KEY_DAS_RIGHT = 3,
+  TETRON_KEY_CW = 4,
+  TETRON_KEY_CCW = 5,
+  TETRON_KEY_ROTATE180 = 6,
+  TETRON_KEY_HARD_DROP = 7,
+  TETRON_KEY_SOFT_DROP = 8,
+  TETRON_KEY_HOLD = 9,
+};
+#ifndef __cplusplus
+typedef uint8_t TetronKey;
+#endif // __cplusplus
+
+/**
+ * Piece codes.
+ */
+enum TetronPiece
+#ifdef __cplusplus
+  : uint8_t
+#endif // __cplusplus
+ {
+  TETRON_PIECE_J = 0,
+  TETRON_PIECE_L = 1,
+  TETRON_PIECE_S = 2,
+  TETRON_PIECE_Z = 3,
+  TETRON_PIECE_T = 4,
+  TETRON_PIECE_I = 5,
+  TETRON_PIECE_O = 6,
+  TETRON_PIECE_NONE = 7,
+};
+#ifndef __cplusplus
+typedef uint8_t TetronPiece;
+#endif // __cplusplus
+
+/**
+ * Opaque bot configuration handle.
+ */
+typedef struct TetronConfig TetronConfig;
+
+/**
+ * Opaque game state handle.
+ */
+typedef struct TetronState TetronState;
+
+/**
+ * Placement selected by `tetron_solve`.
+ *
+ * `x`, `y` are the center of the piece's bounding box, `y` counting rows down from the top of the buffer.
+ * `r` is the rotation: 0 spawn, 1 clockwise, 2 180, 3 counter-clockwise.
+ */
+typedef struct TetronResult {
+  int8_t x;
+  int8_t y;
+  uint8_t r;
+  bool hold;
+  bool tspin;
+  uint8_t key_count;
+  /**
+   * `TetronKey` codes to input, in order.
+   */
+  uint8_t keys[TETRON_MAX_KEYS];
+  float score;
+} TetronResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a configuration. `mode` is 0 for normal, 1 for attack, 2 for downstack.
+ *
+ * Free with `tetron_config_free`.
+ */
+struct TetronConfig *tetron_config_new(uint8_t depth, uint8_t mode);
+
+/**
+ * Stops the `tetron_solve` in flight with `config`, from another thread. It returns the best move
+ * found so far. Each `tetron_solve` clears the flag as it starts.
+ *
+ * # Safety
+ * `config` must be a valid configuration.
+ */
+void tetron_config_cancel(const struct TetronConfig *config);
+
+/**
+ * Frees a configuration.
+ *
+ * # Safety
+ * `config` must come from `tetron_config_new` and not be used afterwards. Null is ignored.
+ */
+void tetron_config_free(struct TetronConfig *config);
+
+/**
+ * Creates an empty state: empty board, queue & hold.
+ *
+ * Free with `tetron_state_free`.
+ */
+struct TetronState *tetron_state_new(void);
+
+/**
+ * Frees a state.
+ *
+ * # Safety
+ * `state` must come from `tetron_state_new` and not be used afterwards. Null is ignored.
+ */
+void tetron_state_free(struct TetronState *state);
+
+/**
+ * Resizes the board to `width` columns (4 to 16) and `height` visible rows (1 to 20), emptying
+ * it. Returns false on an invalid size.
+ *
+ * # Safety
+ * `state` must be a valid state.
+ */
+bool tetron_state_set_size(struct TetronState *state, uint8_t width, uint8_t height);
+
+/**
+ * Sets the board. `rows` holds `len` rows from the top; bit `x` of a row is column `x`.
+ * The board is 40 rows tall, its top 20 rows being the hidden buffer above the visible board.
+ * Fewer rows are aligned to the bottom, columns past the board's width are dropped.
+ * Returns false if `len` exceeds 40.
+ *
+ * # Safety
+ * `state` must be a valid state; `rows` must point to `len` values.
+ */
+bool tetron_state_set_rows(struct TetronState *state, const uint16_t *rows, size_t len);
+
+/**
+ * Sets the queue, current piece first, as `TetronPiece` codes. Returns false on an invalid code.
+ *
+ * # Safety
+ * `state` must be a valid state; `pieces` must point to `len` values.
+ */
+bool tetron_state_set_queue(struct TetronState *state, const uint8_t *pieces, size_t len);
+
+/**
+ * Seeds the 7-bag from the number of pieces dealt so far, the queue included. By default
+ * `tetron_state_set_queue()` infers it from the queue alone.
+ *
+ * # Safety
+ * `state` must be a valid state.
+ */
+void tetron_state_seed_bag(struct TetronState *state, size_t dealt);
+
+/**
+ * Sets the hold piece (`TETRON_PIECE_NONE` for none). Returns false on an invalid code.
+ *
+ * # Safety
+ * `state` must be a valid state.
+ */
+bool tetron_state_set_hold(struct TetronState *state, uint8_t hold);
+
+/**
+ * Sets the back-to-back and combo counters. `b2b` is clamped to 3, the longest chain the
+ * attack table tells apart.
+ *
+ * # Safety
+ * `state` must be a valid state.
+ */
+void tetron_state_set_props(struct TetronState *state, uint8_t b2b, uint8_t combo);
+
+/**
+ * Selects a move for `state`, written into `out`. Returns false if there is no move, if its
+ * inputs do not fit in `TETRON_MAX_KEYS` keys, or if the search fails.
+ *
+ * # Safety
+ * `state` and `config` must be valid handles; `out` must be writable.
+ */
+bool tetron_solve(const struct TetronState *state,
+                  const struct TetronConfig *config,
+                  struct TetronResult *out);
+
+/**
+ * Plays a result of `tetron_solve` on `state`: locks the piece, clears lines & advances the queue.
+ * Returns false, leaving `state` as is, if the placement is invalid: off the board, overlapping
+ * the stack, or placing no piece.
+ *
+ * # Safety
+ * `state` must be a valid state; `result` must point to a result.
+ */
+bool tetron_state_play(struct TetronState *state, const struct TetronResult *result);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* TETRON_H */
@@ -0,0 +1,161 @@
+//! Module isolating the beam search, alternative to the pruned DFS of `solve()`.
+//!
+//! Every ply expands all states of the beam and keeps the `width` best children across all
+//! parents, so the cost of a search is about `depth * width` move generations.
+
+use std::collections::HashSet;
+
+use rayon::prelude::*;
+
+use super::{State, Move, evaluate};
+use super::gen_moves::gen_moves_counted;
+use super::config::Config;
+use super::solve::{Limit, Line};
+use super::stats::Counters;
+use super::transposition::TranspositionTable;
+
+/// State of the beam, with the moves leading to it from the root and their scores.
+struct Node {
+    state: State,
+    line: Vec<(Move, f32)>,
+    score: f32,
+}
+
+/// Evaluated children of a node, `depth` plies before the end of the search.
+fn expand (node: &Node, configs: &Config, depth: u8, limit: &Limit) -> Vec<Node> {
+    let (moves, conflicts) = gen_moves_counted(&node.state);
+    limit.stats.node(depth);
+    Counters::add(&limit.stats.moves, moves.len());
+    Counters::add(&limit.stats.evaluations, moves.len());
+    Counters::add(&limit.stats.conflicts, conflicts as usize);
+    moves.into_iter()
+        .map(|(field, mov)| {
+            let state = node.state.clone_as_child(field, &mov);
+            let score = evaluate(&state, configs.eval_mode);
+            let mut line = node.line.clone();
+            line.push((mov, score));
+            Node { state, line, score }
+        })
+        .collect()
+}
+
+/// Keeps the `width` best nodes, dropping transpositions of the same position.
+fn select (mut nodes: Vec<Node>, configs: &Config, width: usize, limit: &Limit) -> Vec<Node> {
+    nodes.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut seen: HashSet<u64> = HashSet::with_capacity(width);
+    let len = nodes.len();
+    nodes.retain(|n| seen.len() < width && seen.insert(TranspositionTable::key(&n.state, configs.eval_mode)));
+    Counters::add(&limit.stats.pruned, len - nodes.len());
+    nodes
+}
+
+/// Beam search of `configs.depth` plies past the root moves.
+///
+/// Stops early once the budget of `limit` runs out, checked before every expansion, returning the
+/// best of the last complete ply. The root moves are always expanded.
+/// Returns the lines of play leading to the best states found under `n` distinct root moves, best first,
+/// see `solve_pv()`, along with the number of plies completed. Scores along a line are the evaluations
+/// the beam ranked states by, except the first, which takes the score of the line's last state.
+pub(crate) fn search (state: &State, configs: &Config, width: usize, n: usize, limit: &Limit) -> Option<(Vec<Line>, u8)> {
+    let width = width.max(1);
+    let root = Node { state: state.clone(), line: vec![], score: 0.0 };
+    let mut beam = select(expand(&root, configs, configs.depth, limit), configs, width, limit);
+    let mut reached: u8 = 0;
+
+    for ply in 1..=configs.depth {
+        // Out of budget mid-ply, keep the previous ply
+        let Some(children) = beam.par_iter()
+            .map(|n| (!limit.exceeded()).then(|| expand(n, configs, configs.depth - ply, limit)))
+            .collect::<Option<Vec<Vec<Node>>>>() else {
+            break;
+        };
+        let children: Vec<Node> = children.into_iter().flatten().collect();
+        // Every line topped out, keep the previous ply
+        if children.is_empty() {
+            break;
+        }
+        beam = select(children, configs, width, limit);
+        reached = ply;
+    }
+
+    // Best node under each root move, replayed for the states of its line
+    let mut roots: Vec<&Move> = vec![];
+    let mut lines: Vec<Line> = vec![];
+    for node in beam.iter() {
+        if lines.len() >= n || roots.contains(&&node.line[0].0) {
+            continue;
+        }
+        roots.push(&node.line[0].0);
+        let mut line: Line = Vec::with_capacity(node.line.len());
+        let mut prev = state;
+        for (mov, score) in node.line.iter() {
+            let next = prev.play(mov)?;
+            line.push((next, mov.clone(), *score));
+            prev = &line[line.len() - 1].0;
+        }
+        line[0].2 = node.score;
+        lines.push(line);
+    }
+    (!lines.is_empty()).then_some((lines, reached))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvaluatorMode;
+    use crate::config::Search;
+    use crate::solve::solve_deepening;
+    use std::time::Instant;
+
+    #[test]
+    fn beam_search_test () {
+        let state: State = "
+            queue: TIOSZJL  hold: S
+            ##........
+            ###.....##
+            ####...###
+            #####.####
+        ".parse().unwrap();
+
+        for (depth, width) in [(2, 8), (5, 16), (5, 1)] {
+            let configs = Config {
+                search: Search::Beam { width },
+                ..Config::new(depth, EvaluatorMode::Norm)
+            };
+            let start = Instant::now();
+            let ((_, mov, score), reached) = solve_deepening(&state, &configs).unwrap();
+            println!("depth: {}, width: {}, score: {}, dt: {}ms, move: {:?}", depth, width, score, start.elapsed().as_millis(), mov);
+            assert_eq!(reached, depth);
+        }
+
+        // Budget, counted in expanded states, stops the beam at the last complete ply
+        let configs = Config {
+            search: Search::Beam { width: 8 },
+            nodes: Some(20),
+            ..Config::new(5, EvaluatorMode::Norm)
+        };
+        let (_, reached) = solve_deepening(&state, &configs).unwrap();
+        assert_eq!(reached, 2);
+
+        // Even within the first ply, past the deadline or cancelled
+        let cancel = crate::solve::CancelToken::new();
+        cancel.cancel();
+        for configs in [
+            Config { time: Some(std::time::Duration::ZERO), ..configs.clone() },
+            Config { nodes: None, cancel: Some(cancel), ..configs.clone() },
+        ] {
+            let (_, reached) = solve_deepening(&state, &configs).unwrap();
+            assert_eq!(reached, 0);
+        }
+
+        // Out of pieces, the beam keeps the deepest ply it could expand: 7 pieces past the root,
+        // the last one stuck in hold
+        let configs = Config {
+            search: Search::Beam { width: 4 },
+            ..Config::new(12, EvaluatorMode::Norm)
+        };
+        let (_, reached) = solve_deepening(&state, &configs).unwrap();
+        assert_eq!(reached, 6);
+    }
+}
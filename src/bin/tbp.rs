@@ -0,0 +1,51 @@
+//! TBP bot binary. Speaks the Tetris Bot Protocol as JSON lines over stdin/stdout.
+//!
+//! Usage: `tetron-tbp [depth] [time ms]`
+//!
+//! With a time budget, `depth` is the maximum depth of an iterative deepening search.
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use tetron::config::Config;
+use tetron::tbp::{Tbp, BotMessage, FrontendMessage};
+use tetron::EvaluatorMode;
+
+fn send (out: &mut impl Write, msg: &BotMessage) -> io::Result<()> {
+    writeln!(out, "{}", serde_json::to_string(msg)?)?;
+    out.flush()
+}
+
+fn main () -> io::Result<()> {
+    let depth: u8 = std::env::args().nth(1)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(2);
+    let time: Option<u64> = std::env::args().nth(2).and_then(|s| s.parse().ok());
+    let config = Config {
+        time: time.map(Duration::from_millis),
+        ..Config::new(depth, EvaluatorMode::Norm)
+    };
+    let mut bot = Tbp::new(config);
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+    send(&mut stdout, &BotMessage::info())?;
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Unknown messages are ignored, as the protocol requires.
+        let Ok(msg) = serde_json::from_str::<FrontendMessage>(&line) else {
+            continue;
+        };
+        if let FrontendMessage::Quit {} = msg {
+            break;
+        }
+        if let Some(reply) = bot.handle(msg) {
+            send(&mut stdout, &reply)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! Versus match runner. Plays seeded bot-vs-bot games and reports the results.
+//!
+//! Usage: `tetron-versus [-n GAMES] [-a DEPTH:MODE] [-b DEPTH:MODE] [--pieces N] [--delay N] [--pps N]`
+
+use tetron::config::Config;
+use tetron::versus::{Match, Rules};
+use tetron::EvaluatorMode;
+
+fn parse_config (s: &str) -> Result<Config, String> {
+    let (depth, mode) = s.split_once(':').unwrap_or((s, "norm"));
+    let depth: u8 = depth.parse().map_err(|_| format!("invalid depth '{}'", depth))?;
+    let mode: EvaluatorMode = mode.parse()?;
+    Ok(Config::new(depth, mode))
+}
+
+fn parse_args () -> Result<(u64, [Config; 2], Rules), String> {
+    let mut games: u64 = 10;
+    let mut configs = [Config::new(2, EvaluatorMode::Norm), Config::new(2, EvaluatorMode::Norm)];
+    let mut rules = Rules::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "-n" => games = value()?.parse().map_err(|e| format!("{}", e))?,
+            "-a" => configs[0] = parse_config(&value()?)?,
+            "-b" => configs[1] = parse_config(&value()?)?,
+            "--pieces" => rules.max_pieces = value()?.parse().map_err(|e| format!("{}", e))?,
+            "--delay" => rules.garbage_delay = value()?.parse().map_err(|e| format!("{}", e))?,
+            "--pps" => rules.pps = value()?.parse().map_err(|e| format!("{}", e))?,
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+    Ok((games, configs, rules))
+}
+
+fn main () {
+    let (games, configs, rules) = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("usage: tetron-versus [-n GAMES] [-a DEPTH:MODE] [-b DEPTH:MODE] [--pieces N] [--delay N] [--pps N]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut wins = [0u32; 2];
+    let mut apm = [0f32; 2];
+    let mut vs = [0f32; 2];
+    for seed in 0..games {
+        let res = Match::new(seed, configs.clone(), rules.clone()).run();
+        if let Some(w) = res.winner {
+            wins[w] += 1;
+        }
+        for i in 0..2 {
+            apm[i] += res.apm(i) / games as f32;
+            vs[i] += res.vs(i) / games as f32;
+        }
+        println!("game {:>3}: winner: {:>4}, apm: {:>5.1} / {:>5.1}, pieces: {} / {}",
+            seed,
+            res.winner.map_or("draw".to_string(), |w| ["A", "B"][w].to_string()),
+            res.apm(0), res.apm(1),
+            res.stats[0].pieces, res.stats[1].pieces,
+        );
+    }
+
+    println!("=== Versus Result ===");
+    for (i, name) in ["A", "B"].iter().enumerate() {
+        println!("{}: win rate: {:>5.1}%, apm: {:>5.1}, vs: {:>5.1}",
+            name, wins[i] as f32 * 100.0 / games.max(1) as f32, apm[i], vs[i]);
+    }
+    println!("draws: {}", games as u32 - wins[0] - wins[1]);
+}
@@ -0,0 +1,286 @@
+//! Module isolating the asynchronous `Bot` handle.
+//!
+//! The bot owns a worker thread that keeps the game state and searches ahead of time,
+//! so the caller's game loop never blocks on `solve()`.
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use super::{State, Field, Move, Piece};
+use super::solve::CancelToken;
+use super::tree::SearchTree;
+use super::stats::SearchStats;
+use super::config::Config;
+use super::evaluator::EvaluatorMode;
+
+/// Extra information on a move returned by the bot.
+#[derive(Clone, Debug)]
+pub struct Info {
+    /// Score of the selected move.
+    pub score: f32,
+    /// Search depth the move was selected at.
+    pub depth: u8,
+    /// Planned line of play, starting with the selected move. See `solve_pv()`.
+    pub pv: Vec<Move>,
+    /// Statistics of the search behind the move.
+    pub stats: SearchStats,
+}
+
+/// Reason a move could not be polled.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum BotPollState {
+    /// The bot is still thinking, or waits for pieces to think on.
+    Waiting,
+    /// The bot found no possible move (game over), or the worker is gone: it stops when told a
+    /// move it cannot play.
+    Dead,
+}
+
+enum Command {
+    AddPiece(Piece),
+    Reset { field: Field, b2b: u8, combo: u8 },
+    Request(u32),
+    Play(Move),
+}
+
+/// Handle to a bot thinking on a background thread.
+///
+/// Usage mirrors Cold Clear: feed pieces with `add_next_piece`, ask for a move with
+/// `request_next_move`, collect it with `poll_next_move`/`block_next_move`, then confirm
+/// the move actually played with `play_move`.
+pub struct Bot {
+    send: Option<Sender<Command>>,
+    recv: Receiver<Option<(Move, Info)>>,
+    worker: Option<JoinHandle<()>>,
+    cancel: CancelToken,
+}
+
+impl Bot {
+    /// Launches a bot on an empty board.
+    pub fn new (config: Config) -> Self {
+        Self::with_state(State::new(), config)
+    }
+
+    /// Launches a bot from an existing state.
+    ///
+    /// The bot cancels its own searches, replacing any `cancel` token of `config`.
+    pub fn with_state (state: State, mut config: Config) -> Self {
+        let (send, cmds) = mpsc::channel();
+        let (results, recv) = mpsc::channel();
+        let cancel = CancelToken::new();
+        config.cancel = Some(cancel.clone());
+        let worker = thread::spawn(move || run(state, config, cmds, results));
+
+        Self {
+            send: Some(send),
+            recv,
+            worker: Some(worker),
+            cancel,
+        }
+    }
+
+    fn command (&self, cmd: Command) {
+        if let Some(send) = &self.send {
+            // A dead worker is reported through polling.
+            let _ = send.send(cmd);
+        }
+    }
+
+    /// Appends a piece to the end of the queue.
+    pub fn add_next_piece (&self, piece: Piece) {
+        self.command(Command::AddPiece(piece));
+    }
+
+    /// Replaces the board, keeping queue and hold. Used when the game state diverges, e.g. on garbage.
+    ///
+    /// Cancels the search in flight, whose result no longer applies.
+    pub fn reset (&self, field: Field, b2b: u8, combo: u8) {
+        // Cancelled before sending, so the worker's reset of the flag comes after
+        self.cancel.cancel();
+        self.command(Command::Reset { field, b2b, combo });
+    }
+
+    /// Requests a move. `incoming` is the amount of pending garbage, which makes the bot play safer.
+    pub fn request_next_move (&self, incoming: u32) {
+        self.command(Command::Request(incoming));
+    }
+
+    /// Non-blocking check for the requested move.
+    pub fn poll_next_move (&self) -> Result<(Move, Info), BotPollState> {
+        match self.recv.try_recv() {
+            Ok(Some(res)) => Ok(res),
+            Ok(None) | Err(TryRecvError::Disconnected) => Err(BotPollState::Dead),
+            Err(TryRecvError::Empty) => Err(BotPollState::Waiting),
+        }
+    }
+
+    /// Waits for the requested move. `None` if the bot is dead.
+    pub fn block_next_move (&self) -> Option<(Move, Info)> {
+        self.recv.recv().ok().flatten()
+    }
+
+    /// Informs the bot of the move played, advancing its state.
+    ///
+    /// A move the bot cannot play leaves it out of sync with the game, so it dies.
+    pub fn play_move (&self, mov: Move) {
+        self.command(Command::Play(mov));
+    }
+}
+
+impl Drop for Bot {
+    fn drop (&mut self) {
+        // Closing the channel stops the worker.
+        self.send = None;
+        self.cancel.cancel();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Worker loop.
+///
+/// Whenever idle with an unexplored state, the worker solves it ahead of time; the result is
+/// kept until the state changes, so requests are usually answered immediately. Requests on an
+/// empty queue wait for the next piece.
+/// The search tree is kept across moves, see `SearchTree`.
+fn run (state: State, config: Config, cmds: Receiver<Command>, results: Sender<Option<(Move, Info)>>) {
+    let mut tree = SearchTree::new(state);
+    let mut pending: Option<u32> = None;
+    let mut cache: Option<(bool, Option<(Move, Info)>)> = None;
+
+    loop {
+        let can_think = !tree.state().pieces.is_empty();
+        let idle = (pending.is_none() && cache.is_some()) || !can_think;
+        let cmd = if idle {
+            match cmds.recv() {
+                Ok(cmd) => Some(cmd),
+                Err(_) => return,
+            }
+        } else {
+            match cmds.try_recv() {
+                Ok(cmd) => Some(cmd),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        };
+
+        if let Some(cmd) = cmd {
+            match cmd {
+                Command::AddPiece(p) => {
+                    tree.add_next_piece(p);
+                    cache = None;
+                },
+                Command::Reset { field, b2b, combo } => {
+                    if let Some(cancel) = &config.cancel {
+                        cancel.reset();
+                    }
+                    let mut state = tree.state().clone();
+                    state.field = field;
+                    state.props.b2b = b2b;
+                    state.props.combo = combo;
+                    tree = SearchTree::new(state);
+                    cache = None;
+                },
+                Command::Request(incoming) => pending = Some(incoming),
+                Command::Play(mov) => {
+                    if !tree.play_move(&mov) {
+                        return;
+                    }
+                    cache = None;
+                },
+            }
+            // Answer from the cache when it fits the request.
+            if let (Some(incoming), Some((defensive, res))) = (pending, &cache) {
+                if (incoming > 0) == *defensive {
+                    let _ = results.send(res.clone());
+                    pending = None;
+                }
+            }
+            continue;
+        }
+
+        // Think
+        let defensive = pending.is_some_and(|incoming| incoming > 0);
+        let mut configs = config.fit(tree.state());
+        if defensive {
+            configs.eval_mode = EvaluatorMode::DS;
+        }
+        let (res, stats) = tree.solve(&configs);
+        let res = res.map(|(line, depth)| {
+            let (_, mov, score) = line[0].clone();
+            let pv = line.into_iter().map(|(_, mov, _)| mov).collect();
+            (mov, Info { score, depth, pv, stats })
+        });
+        // Searched a stale state, rethink once reset
+        if config.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            continue;
+        }
+
+        if pending.take().is_some() {
+            let _ = results.send(res.clone());
+        }
+        cache = Some((defensive, res));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bot_test () {
+        let bot = Bot::new(Config::new(1, EvaluatorMode::Norm));
+        for p in [Piece::T, Piece::I, Piece::O, Piece::S, Piece::Z] {
+            bot.add_next_piece(p);
+        }
+        assert!(matches!(bot.poll_next_move(), Err(BotPollState::Waiting)));
+
+        bot.request_next_move(0);
+        let (mov, info) = bot.block_next_move().expect("bot found no move");
+        println!("move: {:?}, score: {}", mov, info.score);
+        assert!(mov.lock);
+
+        bot.play_move(mov);
+        bot.add_next_piece(Piece::L);
+        bot.request_next_move(4);
+        assert!(bot.block_next_move().is_some());
+    }
+
+    #[test]
+    fn bot_dead_test () {
+        // An empty queue waits for pieces
+        let bot = Bot::new(Config::new(1, EvaluatorMode::Norm));
+        bot.request_next_move(0);
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(bot.poll_next_move().err(), Some(BotPollState::Waiting));
+        bot.add_next_piece(Piece::T);
+        let (mov, _) = bot.block_next_move().expect("bot found no move");
+
+        // A move the bot cannot play kills it
+        bot.play_move(Move { x: -5, ..mov });
+        bot.add_next_piece(Piece::I);
+        bot.request_next_move(0);
+        assert!(bot.block_next_move().is_none());
+        assert_eq!(bot.poll_next_move().err(), Some(BotPollState::Dead));
+    }
+
+    #[test]
+    fn bot_reset_test () {
+        let bot = Bot::new(Config::new(2, EvaluatorMode::Norm));
+        for p in [Piece::T, Piece::I, Piece::O, Piece::S, Piece::Z] {
+            bot.add_next_piece(p);
+        }
+        // Garbage arrives while the bot thinks ahead
+        let mut state = State::new();
+        state.pieces.extend([Piece::T, Piece::I, Piece::O, Piece::S, Piece::Z]);
+        state.field.m[39] = 0b1111111110;
+        bot.reset(state.field.clone(), 0, 0);
+        bot.request_next_move(0);
+        let (mov, info) = bot.block_next_move().expect("bot found no move");
+        println!("move: {:?}, depth: {}, pv: {}", mov, info.depth, info.pv.len());
+        assert_eq!(info.pv[0], mov);
+        assert!(state.play(&mov).is_some());
+    }
+}
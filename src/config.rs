@@ -1,21 +1,99 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::evaluator::EvaluatorMode;
+use crate::transposition::TranspositionTable;
+use crate::solve::CancelToken;
+use crate::{State, Piece};
+
+/// Pruning of a search node: how many evaluated children are expanded further.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cutoff {
+    /// Children scoring below `best - factor * (best - worst)` are dropped.
+    pub factor: f32,
+    /// Maximum number of children kept.
+    pub keep: usize,
+}
+impl Cutoff {
+    /// Default schedule by remaining depth. Tuned up to depth 3, then narrowing geometrically.
+    pub fn schedule (depth: u8) -> Self {
+        let factor: f32 = match depth {
+            0 | 1 => 0.4,
+            2 => 0.3,
+            _ => (0.25 * 0.85f32.powi(depth as i32 - 3)).max(0.1),
+        };
+        Self {
+            factor,
+            keep: 13usize.saturating_sub(depth as usize).max(4),
+        }
+    }
+}
+
+/// Search algorithm of `solve()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Search {
+    /// Depth-first search, pruned by the cutoff schedule.
+    Dfs,
+    /// Beam search keeping the `width` best states across all parents at each ply.
+    Beam { width: usize },
+}
 
 #[derive(Clone)]
 pub struct Config {
+    /// Search depth. The maximum depth when searching under a time or node budget.
     pub depth: u8,
     pub eval_mode: EvaluatorMode,
+    pub search: Search,
+    /// Time budget per solve. Enables iterative deepening.
+    pub time: Option<Duration>,
+    /// Node budget per solve (states expanded). Enables iterative deepening.
+    pub nodes: Option<u64>,
+    /// Pruning by remaining depth, `cutoffs[depth - 1]`. Depths not covered use `Cutoff::schedule()`.
+    pub cutoffs: Vec<Cutoff>,
+    /// Pieces drawn per chance node, once a line runs out of queue. `None` averages over every
+    /// piece the bag could deal.
+    pub chance_samples: Option<usize>,
+    /// Transposition table shared by the search threads, and across solves.
+    pub tt: Option<Arc<TranspositionTable>>,
+    /// Stops solves from another thread, with the best result found so far. Enables iterative
+    /// deepening.
+    pub cancel: Option<CancelToken>,
 }
 impl Config {
     pub fn new (depth: u8, eval_mode: EvaluatorMode) -> Self {
         Self {
             depth,
             eval_mode,
+            search: Search::Dfs,
+            time: None,
+            nodes: None,
+            cutoffs: vec![],
+            chance_samples: None,
+            tt: None,
+            cancel: None,
         }
     }
+    /// Pruning of nodes with `depth` plies left to search.
+    pub fn cutoff (&self, depth: u8) -> Cutoff {
+        self.cutoffs.get(depth as usize - 1).copied().unwrap_or(Cutoff::schedule(depth))
+    }
+    /// Whether the search is bounded by a time or node budget.
+    pub fn budgeted (&self) -> bool {
+        self.time.is_some() || self.nodes.is_some()
+    }
     pub fn next (&self) -> Self {
         Self {
             depth: self.depth-1,
             ..self.clone()
         }
     }
+    /// Clamps depth to what the queue of `state` can feed, so no branch runs out of pieces
+    /// and into the costlier chance nodes.
+    pub fn fit (&self, state: &State) -> Self {
+        let reserve: usize = if state.hold == Piece::None {2} else {1};
+        Self {
+            depth: (self.depth as usize).min(state.pieces.len().saturating_sub(reserve)) as u8,
+            ..self.clone()
+        }
+    }
 }
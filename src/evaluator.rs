@@ -3,9 +3,10 @@
 
 use super::{State, Field, Props, Piece};
 use crate::mac::*;
+use crate::field::{FIELD_H, FIELD_W, MAX_W};
 
 /// Enumeration representing possible modes for heuristic function
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum EvaluatorMode {
     /// Normal Mode - Evaluator decides mode
     Norm,
@@ -14,12 +15,25 @@ pub enum EvaluatorMode {
     /// Downstack Mode - Prioritizes downstack.
     DS,
 }
+impl std::str::FromStr for EvaluatorMode {
+    type Err = String;
+
+    fn from_str (s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "norm" => Ok(EvaluatorMode::Norm),
+            "attack" | "atk" => Ok(EvaluatorMode::Attack),
+            "ds" => Ok(EvaluatorMode::DS),
+            _ => Err(format!("unknown evaluator mode '{}'", s)),
+        }
+    }
+}
 struct Consts {
     ds_height_threshold: f32,
     ds_hole_threshold: f32,
     ds_mode_penalty: f32,
     well_placement_f: f32,
-    well_placement: [f32; 10],
+    /// By column of the standard board, scaled onto other widths.
+    well_placement: [f32; FIELD_W],
 }
 struct Factors {
     ideal_h: f32,
@@ -107,7 +121,7 @@ const TSPIN_NEG: [u16; 3] = [
 fn tspin_check (state: &State, x: usize, y: usize) -> Option<(u8, u8, usize, usize)> {
     // The x, y point given here is a hole (overhang'ed hole)
     let f: &Field = &state.field;
-    if y < 1 || y > 18 { return None; }
+    if y < 1 || y > FIELD_H - 2 { return None; }
         
     // println!("x: {}, y: {}", x, y);
     // Check overhang depth (must be 1)
@@ -115,7 +129,7 @@ fn tspin_check (state: &State, x: usize, y: usize) -> Option<(u8, u8, usize, usi
         return None;
     }
     // RIGHT
-    let r: Option<(u8, usize, usize)> = if x <= 7 {'block: {
+    let r: Option<(u8, usize, usize)> = if x + 2 < f.width as usize {'block: {
         // Check negative
         for j in 0..3 {
             //println!("row: {:010b} mask: {:010b}", f.m[y+j-1], TSPIN_NEG[j] << x);
@@ -124,7 +138,7 @@ fn tspin_check (state: &State, x: usize, y: usize) -> Option<(u8, u8, usize, usi
             }
         }
         // Check bottom far notch (impossible setup if only one is filled)
-        if x < 7 && (f.m[y+1] & (1 << x+2) > 0) ^ ((f.m[y+1] & (1 << x+3) > 0)) {
+        if x + 3 < f.width as usize && (f.m[y+1] & (1 << x+2) > 0) ^ ((f.m[y+1] & (1 << x+3) > 0)) {
             break 'block None;
         } else if f.m[y+1] & (1 << x+2) == 0 {
             break 'block None;
@@ -132,8 +146,8 @@ fn tspin_check (state: &State, x: usize, y: usize) -> Option<(u8, u8, usize, usi
         // println!("passed right @({}, {})", x, y);
         // check cleared rows
         Some((
-            if f.m[y]   == ((1 << 10) - 1) ^ (0b111 << x) {1} else {0} + 
-            if f.m[y+1] == ((1 << 10) - 1) ^ (0b010 << x) {1} else {0},
+            if f.m[y]   == f.full_row() ^ (0b111 << x) {1} else {0} + 
+            if f.m[y+1] == f.full_row() ^ (0b010 << x) {1} else {0},
             x + 1, y
         ))
     }} else { None };
@@ -156,8 +170,8 @@ fn tspin_check (state: &State, x: usize, y: usize) -> Option<(u8, u8, usize, usi
         //println!("passed left @({}, {})", x, y);
         // check cleared rows
         Some((
-            if f.m[y]   == ((1 << 10) - 1) ^ (0b111 << x-2) {1} else {0} + 
-            if f.m[y+1] == ((1 << 10) - 1) ^ (0b010 << x-2) {1} else {0},
+            if f.m[y]   == f.full_row() ^ (0b111 << x-2) {1} else {0} + 
+            if f.m[y+1] == f.full_row() ^ (0b010 << x-2) {1} else {0},
             x-1, y
         ))
     }} else { None };
@@ -165,6 +179,10 @@ fn tspin_check (state: &State, x: usize, y: usize) -> Option<(u8, u8, usize, usi
     // Calculate dist from T piece.
     let depth = {
         let mut depth = if state.hold == Piece::T {1} else {7};
+        // Past the queue, a T left in the bag comes by the end of it at the latest
+        if state.hold != Piece::T && state.bag & (1 << Piece::T as u8) > 0 {
+            depth = depth.min(state.pieces.len() + state.bag.count_ones() as usize);
+        }
         for i in 0..state.pieces.len() {
             if state.pieces[i] == Piece::T {
                 depth = i + 1;
@@ -191,10 +209,13 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
 
     let f: &Field = &state.field;
     let p: &Props = &state.props;
+    if p.top_out {
+        return f32::NEG_INFINITY;
+    }
     let mut score: f32 = 0.0;
-    const FW: usize = 10;
-    const FH: usize = 20;
-    let mut h: [u8; FW] = [0; FW];
+    const FH: usize = FIELD_H;
+    let fw: usize = f.width as usize;
+    let mut h: [u8; MAX_W] = [0; MAX_W];
     let mut well: Option<usize> = None;
 
     // get all column heights
@@ -203,10 +224,10 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
         while cache_y < FH && f.m[cache_y] == 0 {
             cache_y += 1;
         }
-        for x in 0..FW {
+        for x in 0..fw {
             for y in cache_y..=FH {
                 if y == FH {
-                    h[x] = 20;
+                    h[x] = FH as u8;
                 } else if f.m[y] & ( 1 << x ) > 0 {
                     h[x] = y as u8;
                     break;
@@ -216,7 +237,10 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
         dev_log!(ln, "h: {:?}", h);
     }
     // Get raw avg height 
-    let mut avg: f32 = h.iter().sum::<u8>() as f32 / FW as f32;
+    let mut avg: f32 = h[..fw].iter().map(|&y| y as f32).sum::<f32>() / fw as f32;
+    // Pending garbage raises the whole stack once it lands
+    let incoming: f32 = state.incoming() as f32;
+    dev_log!(ln, "incoming: {}", incoming);
 
     // find holes
     let (holes, hole_depth_sum_sq, tspin) = {
@@ -224,7 +248,7 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
         let mut depth_sum_sq: f32 = 0.0;
         let mut tspin: Option<(u8, u8, usize, usize)> = None; // (dist from T, clearable rows);
 
-        for x in 0..FW {
+        for x in 0..fw {
             for y in (h[x] as usize + 1)..FH {
                 if ( f.m[y] & ( 1 << x ) ) == 0 {
                     if let Some(_tspin) = tspin_check(&state, x, y) {
@@ -253,7 +277,7 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
     let (weights, factors) = {
         match mode {
             EvaluatorMode::Norm => 
-                if  FH as f32 - avg >= CONSTS.ds_height_threshold || holes >= CONSTS.ds_hole_threshold {
+                if  FH as f32 - avg + incoming >= CONSTS.ds_height_threshold || holes >= CONSTS.ds_hole_threshold {
                     dev_log!(ln, "DS penalty: {}", CONSTS.ds_mode_penalty);
                     score += CONSTS.ds_mode_penalty;
                     (WEIGHTS_DS, FACTORS_DS)
@@ -281,7 +305,7 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
     }
     // Find well (max neg deviation from avg > than threshold)
     {
-        for x in 0..10 {
+        for x in 0..fw {
             let d: f32 = avg - h[x] as f32;
             if d < 0.0 && d.abs() >= factors.well_threshold {
                 if let Some(pwell) = well {
@@ -294,14 +318,14 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
             }
         }
         if let Some(well) = well {
-            avg = (avg * FW as f32 - h[well] as f32) / (FW - 1) as f32;
+            avg = (avg * fw as f32 - h[well] as f32) / (fw - 1) as f32;
             dev_log!(ln, "identified well: \x1b[1m{}\x1b[0m", well);
         }
     }
 
     // Score by avg height
     { 
-        let h: f32 = FH as f32 - avg;
+        let h: f32 = FH as f32 - avg + incoming;
         let d: f32 = (h - factors.ideal_h).abs();
         score += weights.average_h * d * d;
         dev_log!(ln, "global h: {}, ideal: {}, penalty: {}", h, factors.ideal_h, d * d * weights.average_h); 
@@ -311,7 +335,7 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
     {
         dev_log!("global h-deviations: ");
         let mut sum_sq: f32 = 0.0;
-        for x in 0..FW {
+        for x in 0..fw {
             if let Some(w) = well { // Ignore if well
                 if w == x {
                     dev_log!("w ");
@@ -336,18 +360,18 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
         dev_log!("local h-deviation: ");
         let mut sum_sq: f32 = 0.0;
         let mut prev: Option<u8> = None;
-        for x in 0..FW {
+        for x in 0..fw {
             // Score well by height (not clear value)
             if let Some(w) = well { if x == w {
-                let well_v = (0..20).fold(0, |y, _| if f.m[y] == ((1 << 10)-1) - (1 << w) {1} else {0});
+                let well_v = (0..FH).fold(0, |y, _| if f.m[y] == f.full_row() - (1 << w) {1} else {0});
 
                 //let well_v = (if x != 0 {h[x-1]} else {20}).min(if x != 9 {h[x+1]} else {20}).abs_diff(h[x]);
                 score += well_v as f32 * weights.well_v;
-                score += CONSTS.well_placement_f * CONSTS.well_placement[x];
+                score += CONSTS.well_placement_f * CONSTS.well_placement[x * FIELD_W / fw];
                 dev_log!("w ");
 
                 // Parity: penalize large parity diffs, bonus for flat well.
-                let d = (if x != 0 {h[x-1]} else {h[x+1]}).abs_diff(if x != 9 {h[x+1]} else {h[x-1]});
+                let d = (if x != 0 {h[x-1]} else {h[x+1]}).abs_diff(if x != fw - 1 {h[x+1]} else {h[x-1]});
                 
                 // Tspins: Subtract one from delta, due to inherent odd parity. 
                 //         Promote an even-residue overhang for better contiuation.
@@ -403,11 +427,12 @@ pub fn evaluate (state: &State, mode: EvaluatorMode) -> f32 {
 #[cfg(test)] 
 mod test {
     use super::*;   
+    use crate::field::BUFFER_H;
     
     #[test]
     fn eval_test () {
         let mut field = Field::new();
-        field.m = [   
+        field.m[BUFFER_H..].copy_from_slice(&[   
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -428,7 +453,7 @@ mod test {
             0b0_0_0_0_1_1_0_0_0_0,
             0b0_0_0_0_0_1_1_1_1_1,
             0b0_1_1_1_1_1_1_1_1_1,
-        ];
+        ]);
         let mut state = State::new();
         
         state.field = field;
@@ -446,7 +471,7 @@ mod test {
     #[test]
     fn tspin_check_test () {
         let mut field = Field::new();
-        field.m = [   
+        field.m[BUFFER_H..].copy_from_slice(&[   
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -467,7 +492,7 @@ mod test {
             0b0_0_0_0_1_1_0_0_0_0,
             0b0_0_0_0_0_1_1_1_1_1,
             0b0_1_1_1_1_1_1_1_1_1,
-        ];
+        ]);
         let mut state = State::new();
         
         state.field = field;
@@ -477,7 +502,7 @@ mod test {
             let f = &state.field;
             let mut out: Option<(u8, u8, usize, usize)> = None;
             for x in 0..10 {
-                for y in 0..20 {
+                for y in 0..FIELD_H {
                     if ( f.m[y] & ( 1 << x ) ) == 0 {
                         if let Some(_tspin) = tspin_check(&state, x, y) {
                             if let Some(ptspin) = out {
@@ -0,0 +1,371 @@
+//! Module implementing the C ABI.
+//!
+//! Built with the `ffi` feature, which also generates the C header from this module alone.
+//! States & configs are opaque handles, created & freed through this API.
+
+use std::slice;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::{State, Piece, Key, solve};
+use super::config::Config;
+use super::solve::CancelToken;
+use super::evaluator::EvaluatorMode;
+use super::field::{Field, FIELD_H, MIN_W, MAX_W, VISIBLE_H};
+
+/// Opaque game state handle.
+pub struct TetronState {
+    state: State,
+}
+
+/// Opaque bot configuration handle.
+pub struct TetronConfig {
+    config: Config,
+}
+
+/// Maximum amount of keys in a `TetronResult`, as many as a move records.
+pub const TETRON_MAX_KEYS: usize = 15;
+
+/// Piece codes.
+#[repr(u8)]
+pub enum TetronPiece {
+    J = 0,
+    L = 1,
+    S = 2,
+    Z = 3,
+    T = 4,
+    I = 5,
+    O = 6,
+    None = 7,
+}
+
+/// Key codes, as listed in `TetronResult.keys`.
+#[repr(u8)]
+pub enum TetronKey {
+    Left = 0,
+    Right = 1,
+    DasLeft = 2,
+    DasRight = 3,
+    Cw = 4,
+    Ccw = 5,
+    Rotate180 = 6,
+    HardDrop = 7,
+    SoftDrop = 8,
+    Hold = 9,
+}
+
+/// Placement selected by `tetron_solve`.
+///
+/// `x`, `y` are the center of the piece's bounding box, `y` counting rows down from the top of the buffer.
+/// `r` is the rotation: 0 spawn, 1 clockwise, 2 180, 3 counter-clockwise.
+#[repr(C)]
+pub struct TetronResult {
+    pub x: i8,
+    pub y: i8,
+    pub r: u8,
+    pub hold: bool,
+    pub tspin: bool,
+    pub key_count: u8,
+    /// `TetronKey` codes to input, in order.
+    pub keys: [u8; TETRON_MAX_KEYS],
+    pub score: f32,
+}
+
+fn piece (code: u8) -> Option<Piece> {
+    Piece::ALL.get(code as usize).copied()
+}
+
+/// Creates a configuration. `mode` is 0 for normal, 1 for attack, 2 for downstack.
+///
+/// Free with `tetron_config_free`.
+#[no_mangle]
+pub extern "C" fn tetron_config_new (depth: u8, mode: u8) -> *mut TetronConfig {
+    let mode = match mode {
+        1 => EvaluatorMode::Attack,
+        2 => EvaluatorMode::DS,
+        _ => EvaluatorMode::Norm,
+    };
+    let config = Config {
+        cancel: Some(CancelToken::new()),
+        ..Config::new(depth, mode)
+    };
+    Box::into_raw(Box::new(TetronConfig { config }))
+}
+
+/// Stops the `tetron_solve` in flight with `config`, from another thread. It returns the best move
+/// found so far. Each `tetron_solve` clears the flag as it starts.
+///
+/// # Safety
+/// `config` must be a valid configuration.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_config_cancel (config: *const TetronConfig) {
+    if let Some(cancel) = config.as_ref().and_then(|c| c.config.cancel.as_ref()) {
+        cancel.cancel();
+    }
+}
+
+/// Frees a configuration.
+///
+/// # Safety
+/// `config` must come from `tetron_config_new` and not be used afterwards. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_config_free (config: *mut TetronConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// Creates an empty state: empty board, queue & hold.
+///
+/// Free with `tetron_state_free`.
+#[no_mangle]
+pub extern "C" fn tetron_state_new () -> *mut TetronState {
+    Box::into_raw(Box::new(TetronState { state: State::new() }))
+}
+
+/// Frees a state.
+///
+/// # Safety
+/// `state` must come from `tetron_state_new` and not be used afterwards. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_free (state: *mut TetronState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+    }
+}
+
+/// Resizes the board to `width` columns (4 to 16) and `height` visible rows (1 to 20), emptying
+/// it. Returns false on an invalid size.
+///
+/// # Safety
+/// `state` must be a valid state.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_set_size (state: *mut TetronState, width: u8, height: u8) -> bool {
+    let (Some(state), true, true) = (
+        state.as_mut(),
+        (MIN_W..=MAX_W).contains(&(width as usize)),
+        (1..=VISIBLE_H).contains(&(height as usize)),
+    ) else {
+        return false;
+    };
+    state.state.field = Field::with_size(width as usize, height as usize);
+    true
+}
+
+/// Sets the board. `rows` holds `len` rows from the top; bit `x` of a row is column `x`.
+/// The board is 40 rows tall, its top 20 rows being the hidden buffer above the visible board.
+/// Fewer rows are aligned to the bottom, columns past the board's width are dropped.
+/// Returns false if `len` exceeds 40.
+///
+/// # Safety
+/// `state` must be a valid state; `rows` must point to `len` values.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_set_rows (state: *mut TetronState, rows: *const u16, len: usize) -> bool {
+    let (Some(state), false) = (state.as_mut(), rows.is_null() || len > FIELD_H) else {
+        return false;
+    };
+    let rows = slice::from_raw_parts(rows, len);
+    state.state.field.m = [0; FIELD_H];
+    for (y, row) in rows.iter().enumerate() {
+        state.state.field.m[FIELD_H - len + y] = row & state.state.field.full_row();
+    }
+    true
+}
+
+/// Sets the queue, current piece first, as `TetronPiece` codes. Returns false on an invalid code.
+///
+/// # Safety
+/// `state` must be a valid state; `pieces` must point to `len` values.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_set_queue (state: *mut TetronState, pieces: *const u8, len: usize) -> bool {
+    let (Some(state), false) = (state.as_mut(), pieces.is_null() && len > 0) else {
+        return false;
+    };
+    let codes = if len == 0 { &[][..] } else { slice::from_raw_parts(pieces, len) };
+    let Some(queue) = codes.iter().map(|&c| piece(c)).collect::<Option<Vec<Piece>>>() else {
+        return false;
+    };
+    state.state.pieces = queue.into();
+    state.state.infer_bag();
+    true
+}
+
+/// Seeds the 7-bag from the number of pieces dealt so far, the queue included. By default
+/// `tetron_state_set_queue()` infers it from the queue alone.
+///
+/// # Safety
+/// `state` must be a valid state.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_seed_bag (state: *mut TetronState, dealt: usize) {
+    if let Some(state) = state.as_mut() {
+        state.state.seed_bag(dealt);
+    }
+}
+
+/// Sets the hold piece (`TETRON_PIECE_NONE` for none). Returns false on an invalid code.
+///
+/// # Safety
+/// `state` must be a valid state.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_set_hold (state: *mut TetronState, hold: u8) -> bool {
+    let Some(state) = state.as_mut() else {
+        return false;
+    };
+    state.state.hold = match piece(hold) {
+        Some(p) => p,
+        None if hold == TetronPiece::None as u8 => Piece::None,
+        None => return false,
+    };
+    true
+}
+
+/// Sets the back-to-back and combo counters. `b2b` is clamped to 3, the longest chain the
+/// attack table tells apart.
+///
+/// # Safety
+/// `state` must be a valid state.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_set_props (state: *mut TetronState, b2b: u8, combo: u8) {
+    if let Some(state) = state.as_mut() {
+        state.state.props.b2b = b2b.min(3);
+        state.state.props.combo = combo;
+    }
+}
+
+/// Selects a move for `state`, written into `out`. Returns false if there is no move, if its
+/// inputs do not fit in `TETRON_MAX_KEYS` keys, or if the search fails.
+///
+/// # Safety
+/// `state` and `config` must be valid handles; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_solve (state: *const TetronState, config: *const TetronConfig, out: *mut TetronResult) -> bool {
+    let (Some(state), Some(config), false) = (state.as_ref(), config.as_ref(), out.is_null()) else {
+        return false;
+    };
+    if let Some(cancel) = &config.config.cancel {
+        cancel.reset();
+    }
+    let Ok(Some((_, mov, score))) = panic::catch_unwind(AssertUnwindSafe(|| solve(&state.state, &config.config.fit(&state.state)))) else {
+        return false;
+    };
+
+    let list = mov.parse_list();
+    if list.len() > TETRON_MAX_KEYS {
+        return false;
+    }
+    let mut keys = [0; TETRON_MAX_KEYS];
+    for (k, key) in keys.iter_mut().zip(list.iter()) {
+        *k = match key {
+            Key::Left => TetronKey::Left,
+            Key::Right => TetronKey::Right,
+            Key::DASLeft => TetronKey::DasLeft,
+            Key::DASRight => TetronKey::DasRight,
+            Key::Cw => TetronKey::Cw,
+            Key::Ccw => TetronKey::Ccw,
+            Key::_180 => TetronKey::Rotate180,
+            Key::HardDrop => TetronKey::HardDrop,
+            Key::SoftDrop => TetronKey::SoftDrop,
+            Key::Hold => TetronKey::Hold,
+        } as u8;
+    }
+    out.write(TetronResult {
+        x: mov.x,
+        y: mov.y,
+        r: mov.r,
+        hold: mov.hold,
+        tspin: mov.tspin,
+        key_count: list.len() as u8,
+        keys,
+        score,
+    });
+    true
+}
+
+/// Plays a result of `tetron_solve` on `state`: locks the piece, clears lines & advances the queue.
+/// Returns false, leaving `state` as is, if the placement is invalid: off the board, overlapping
+/// the stack, or placing no piece.
+///
+/// # Safety
+/// `state` must be a valid state; `result` must point to a result.
+#[no_mangle]
+pub unsafe extern "C" fn tetron_state_play (state: *mut TetronState, result: *const TetronResult) -> bool {
+    let (Some(state), Some(result)) = (state.as_mut(), result.as_ref()) else {
+        return false;
+    };
+    let mov = crate::Move {
+        x: result.x,
+        y: result.y,
+        r: result.r,
+        hold: result.hold,
+        tspin: result.tspin,
+        lock: true,
+        ..crate::Move::new()
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| state.state.play(&mov))) {
+        Ok(Some(child)) => {
+            state.state = child;
+            true
+        },
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi_solve_test () {
+        unsafe {
+            let config = tetron_config_new(1, 0);
+            let state = tetron_state_new();
+
+            let rows: [u16; 2] = [0b0_0_0_0_0_1_1_1_1_1, 0b0_1_1_1_1_1_1_1_1_1];
+            assert!(tetron_state_set_rows(state, rows.as_ptr(), rows.len()));
+            assert!(!tetron_state_set_rows(state, rows.as_ptr(), 41));
+            assert_eq!((*state).state.field.m[39], rows[1]);
+            assert!(!tetron_state_set_size(state, 17, 20));
+            assert!(tetron_state_set_size(state, 4, 20));
+            assert!(tetron_state_set_rows(state, rows.as_ptr(), rows.len()));
+            assert_eq!((*state).state.field.m[39], 0b1_1_1_1);
+            assert!(tetron_state_set_size(state, 10, 20));
+            assert!(tetron_state_set_rows(state, rows.as_ptr(), rows.len()));
+
+            let queue = [TetronPiece::T as u8, TetronPiece::I as u8, TetronPiece::O as u8];
+            assert!(tetron_state_set_queue(state, queue.as_ptr(), queue.len()));
+            assert!(!tetron_state_set_queue(state, [9u8].as_ptr(), 1));
+            assert!(tetron_state_set_hold(state, TetronPiece::None as u8));
+            tetron_state_set_props(state, 200, 0);
+            assert_eq!((*state).state.props.b2b, 3);
+            tetron_state_set_props(state, 1, 0);
+
+            let mut out = std::mem::MaybeUninit::<TetronResult>::uninit();
+            assert!(tetron_solve(state, config, out.as_mut_ptr()));
+            let out = out.assume_init();
+            assert!(out.key_count > 0);
+            assert_eq!(out.keys[out.key_count as usize - 1], TetronKey::HardDrop as u8);
+            assert!(tetron_state_play(state, &out));
+            assert_eq!((*state).state.pieces.len(), if out.hold {1} else {2});
+
+            // Invalid placements are rejected, leaving the state as is
+            let field = (*state).state.field.clone();
+            for (x, y, r) in [(-1, 20, 0), (12, 20, 0), (4, 20, 4), (4, 60, 0)] {
+                assert!(!tetron_state_play(state, &TetronResult { x, y, r, ..out }));
+            }
+            assert_eq!((*state).state.field, field);
+
+            assert!(!tetron_solve(std::ptr::null(), config, std::ptr::null_mut()));
+            tetron_state_free(state);
+            tetron_config_free(config);
+            tetron_state_free(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn ffi_header_test () {
+        // Regenerate with `cp $OUT_DIR/tetron.h include/` after changing the API.
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/tetron.h"));
+        assert!(generated == include_str!("../include/tetron.h"), "include/tetron.h is out of date, see {}", env!("OUT_DIR"));
+        assert!(!generated.contains("FIELD_H"));
+    }
+}
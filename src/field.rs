@@ -1,21 +1,42 @@
 use super::{Piece, Move, Props};
+use super::rng::Rng;
 
 use std::fmt;
-
-pub static mut COUNTER: u128 = 0;
-/// Effective allias for `[u16; 20]`, representing the game board.
+use std::str::FromStr;
+
+/// Columns of the standard board.
+pub const FIELD_W: usize = 10;
+/// Columns of the widest board, as many as a row of `Field.m` holds.
+pub const MAX_W: usize = 16;
+/// Columns of the narrowest board, fitting a flat I piece.
+pub const MIN_W: usize = 4;
+/// Rows of `Field.m`: the tallest board, hidden buffer included.
+pub const FIELD_H: usize = 40;
+/// Rows of the standard visible board, which is also the tallest.
+pub const VISIBLE_H: usize = 20;
+/// Hidden rows above the visible board. Pieces spawn in its bottom rows.
+pub const BUFFER_H: usize = FIELD_H - VISIBLE_H;
+
+/// Effective allias for `[u16; FIELD_H]`, representing the game board.
 /// 
 /// Minial memory footprint.
 /// Implements getting, setting, and helper functions.
-#[derive(PartialEq, Eq, Hash, Clone)]
+/// The board sits at the bottom of `m`: its `height` visible rows under `BUFFER_H` rows of
+/// hidden buffer. Rows above the buffer and columns past `width` are walls.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct Field {
-    pub m: [u16; 20],
+    pub m: [u16; FIELD_H],
+    /// Columns, from `MIN_W` to `MAX_W`.
+    pub width: u8,
+    /// Visible rows, up to `VISIBLE_H`.
+    pub height: u8,
 }
-pub type ConflictCache = [[u32; 20]; 4];
+/// Per rotation & row, bit `x` flags a conflict at column `x`, bit `MAX_W + x` that it was computed.
+pub type ConflictCache = [[u32; FIELD_H]; 4];
 impl fmt::Display for Field {
     fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result { 
-        for y in 0..20 {
-            for x in 0..10 {
+        for y in self.top()..FIELD_H {
+            for x in 0..self.width {
                 let b: bool = (self.m[y] & (1 << x)) >> x == 1;
                 if b {
                     write!(f, "# ")?;
@@ -29,15 +50,142 @@ impl fmt::Display for Field {
     }
 }
 
+/// Error raised when parsing a board diagram.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ParseError {
+    /// Grid row (0-indexed, from the top of the diagram) not holding as many cells as the first,
+    /// or too few or too many cells for a board.
+    Row(usize),
+    /// Diagram taller than the board.
+    Height,
+    /// Unrecognized side panel or header entry.
+    Value(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Row(y) => write!(f, "row {} does not match the width of the board", y),
+            ParseError::Height => write!(f, "board has more than {} rows", FIELD_H),
+            ParseError::Value(v) => write!(f, "invalid entry '{}'", v),
+        }
+    }
+}
+impl std::error::Error for ParseError {}
+
+/// Parses a board diagram into a field, collecting the text beside and above the grid.
+///
+/// Grid rows are lines starting with `#` (filled) or `.` (empty) cells, spaces optional.
+/// The first row sets the width of the board, which is `VISIBLE_H` rows tall.
+/// Diagrams shorter than the board are aligned to the bottom.
+pub(crate) fn parse_diagram (s: &str) -> Result<(Field, Vec<&str>), ParseError> {
+    let mut rows: Vec<u16> = vec![];
+    let mut text: Vec<&str> = vec![];
+    let mut width: Option<usize> = None;
+
+    for line in s.lines() {
+        if !line.trim_start().starts_with(['#', '.']) {
+            text.push(line.trim());
+            continue;
+        }
+        let mut row: u16 = 0;
+        let mut cells: usize = 0;
+        let mut rest: &str = "";
+        for (i, c) in line.char_indices() {
+            match c {
+                '#' | '.' if cells < MAX_W => {
+                    if c == '#' {
+                        row |= 1 << cells;
+                    }
+                    cells += 1;
+                },
+                _ if c.is_whitespace() => (),
+                _ => {
+                    rest = &line[i..];
+                    break;
+                }
+            }
+        }
+        let width = *width.get_or_insert(cells);
+        if cells != width || width < MIN_W || rest.starts_with(['#', '.']) {
+            return Err(ParseError::Row(rows.len()));
+        }
+        rows.push(row);
+        text.push(rest.trim());
+    }
+    if rows.len() > FIELD_H {
+        return Err(ParseError::Height);
+    }
+
+    let mut field = Field::with_size(width.unwrap_or(FIELD_W), VISIBLE_H);
+    field.m[FIELD_H - rows.len()..].copy_from_slice(&rows);
+    Ok((field, text))
+}
+
+impl FromStr for Field {
+    type Err = ParseError;
+
+    /// Parses the diagram produced by `Display`. Text around the grid is ignored.
+    fn from_str (s: &str) -> Result<Self, Self::Err> {
+        parse_diagram(s).map(|(field, _)| field)
+    }
+}
+
 // (c_x, c_y) denotes the corner of the map. This is what is typically used in computation
 // (x, y) denotes the center of the map. This is what is stored in Moves
 impl Field {
+    /// Spawns the standard 10 by 20 board.
     pub fn new () -> Self {
+        Self::with_size(FIELD_W, VISIBLE_H)
+    }
+
+    /// Spawns a board of `width` columns and `height` visible rows.
+    ///
+    /// Panics if `width` is outside `MIN_W..=MAX_W`, or `height` outside `1..=VISIBLE_H`.
+    pub fn with_size (width: usize, height: usize) -> Self {
+        assert!((MIN_W..=MAX_W).contains(&width), "board width {} out of {}..={}", width, MIN_W, MAX_W);
+        assert!((1..=VISIBLE_H).contains(&height), "board height {} out of 1..={}", height, VISIBLE_H);
         Self {
-            m: [0; 20],
+            m: [0; FIELD_H],
+            width: width as u8,
+            height: height as u8,
         }
     }
 
+    /// A full row of the board.
+    pub fn full_row (&self) -> u16 {
+        u16::MAX >> (MAX_W - self.width as usize)
+    }
+
+    /// First row of the board: the top of its buffer.
+    pub fn ceiling (&self) -> usize {
+        FIELD_H - BUFFER_H - self.height as usize
+    }
+
+    /// First row of the visible board.
+    pub fn visible_top (&self) -> usize {
+        FIELD_H - self.height as usize
+    }
+
+    /// Spawn position: centered, left of center on even widths, in the bottom rows of the buffer.
+    pub fn spawn (&self) -> Move {
+        Move {
+            x: (self.width as i8 - 1) / 2,
+            y: self.visible_top() as i8 - 1,
+            ..Move::new()
+        }
+    }
+
+    /// First non-empty row, or `FIELD_H` if the field is empty.
+    pub fn stack_top (&self) -> usize {
+        self.m.iter().position(|&row| row != 0).unwrap_or(FIELD_H)
+    }
+
+    /// First row to display: the top of the visible board, or of the stack if it reaches into the buffer.
+    pub fn top (&self) -> usize {
+        self.stack_top().min(self.visible_top())
+    }
+
     pub fn check_conflict(&self, cache: &mut ConflictCache, m: &Move, p: &Piece) -> bool {
         let _bencher: Option<crate::Bencher> = if cfg!(feature = "bench") {
             unsafe {
@@ -45,11 +193,11 @@ impl Field {
             }
         } else {None};
 
-       if m.y < 0 || m.y >= 20 || m.x < 0 || m.x >= 10 {
+       if m.y < 0 || m.y >= FIELD_H as i8 || m.x < 0 || m.x >= self.width as i8 {
             return true;
         }
-        if cache[m.r as usize][m.y as usize] & 1 << (10 + m.x as usize) == 0 {
-            cache[m.r as usize][m.y as usize] |= 1 << (10 + m.x as usize);
+        if cache[m.r as usize][m.y as usize] & 1 << (MAX_W + m.x as usize) == 0 {
+            cache[m.r as usize][m.y as usize] |= 1 << (MAX_W + m.x as usize);
             if self.compute_conflict(m, p) {
                 cache[m.r as usize][m.y as usize] |= 1 << (m.x as usize);
             }
@@ -74,27 +222,27 @@ impl Field {
                 continue;
             }
             // If out of board on upper edge
-            if  c_y + y < 0 {
+            if  c_y + y < self.ceiling() as i8 {
                 //continue;
                 return true;
             }
             // If out of board on bottom edge
-            if c_y + y >= 20 {
+            if c_y + y >= FIELD_H as i8 {
                 return true
             }
             // If out of board on left edge
             if c_x < 0 && bitseg & ((1 << (-c_x)) - 1) > 0  {
                 return true
             }
-            // Shift according to c_x
-            let bitseg = if c_x > 0 { bitseg << c_x } else { bitseg >> -c_x };
+            // Shift according to c_x, wide enough to keep cells past a 16-wide board
+            let bitseg = if c_x > 0 { (bitseg as u32) << c_x } else { (bitseg >> -c_x) as u32 };
 
             // If out of board on right edge
-            if  bitseg > (1 << 10) -1 {
+            if  bitseg > self.full_row() as u32 {
                 return true
             }
 
-            if self.m[(c_y + y) as usize] & bitseg > 0 {
+            if self.m[(c_y + y) as usize] as u32 & bitseg > 0 {
                 return true
             }
         };
@@ -102,10 +250,13 @@ impl Field {
     }   
 
     /// Pastes a given piece onto a clone of self according to given move, returning said clone.
+    ///
+    /// Errs if there is no piece, the rotation is invalid or the piece lies off the board.
+    /// Overlapping filled cells is not checked, see `check_conflict()`.
     pub fn apply_move (self: &Self, m: &Move, piece: &Piece, hold: &Piece) -> Result<Field, ()> {
         let mut field = self.clone();
         let p: &Piece = if m.hold {hold} else {piece};
-        let map: &[u16; 5] = &PIECE_MAP[*p as usize][m.r as usize];
+        let map: &[u16; 5] = PIECE_MAP.get(*p as usize).and_then(|maps| maps.get(m.r as usize)).ok_or(())?;
         let n: i8 = if *p == Piece::I {5} else {3};
         let c_x: i8 = m.x - n/2;
         let c_y: i8 = m.y - n/2;
@@ -121,29 +272,25 @@ impl Field {
                 continue;
             }
             // If out of board on upper edge
-            if  c_y + y < 0 {
-                //return Err(());
-                panic!("@ Field.apply_move: out of board on upper edge");
+            if  c_y + y < self.ceiling() as i8 {
+                return Err(());
             }
             // If out of board on bottom edge
-            if c_y + y >= 20 {
-                //return Err(());
-                panic!("@ Field.apply_move: out of board on bottom edge");
+            if c_y + y >= FIELD_H as i8 {
+                return Err(());
             }
             // If out of board on left edge
             if c_x < 0 && bitseg & ((1 << (-c_x)) - 1) > 0  {
-                //return Err(());
-                panic!("@ Field.apply_move: out of board on left edge");
+                return Err(());
             }
             // Shift according to c_x
-            let bitseg = if c_x > 0 { bitseg << c_x } else { bitseg >> -c_x };
+            let bitseg = if c_x > 0 { (bitseg as u32) << c_x } else { (bitseg >> -c_x) as u32 };
             //dev_log!("c_x: {}, final bitseg: {:05b}", c_x, bitseg);
             // If out of board on right edge
-            if bitseg > (1 << 10)-1 {
-                //return Err(());
-                panic!("@ Field.apply_move: out of board on right edge");
+            if bitseg > self.full_row() as u32 {
+                return Err(());
             }
-            field.m[(c_y + y) as usize] |= bitseg;
+            field.m[(c_y + y) as usize] |= bitseg as u16;
         };
         //dev_log!("{}", field);
         Ok(field)
@@ -157,11 +304,11 @@ impl Field {
     pub fn set_props (self: &mut Self, mov: &Move, props: &mut Props) {
         // Clear rows
         let mut clears: usize = 0;
-        for y in (0..20).rev() {
+        for y in (0..FIELD_H).rev() {
             if clears > 0 {
                 self.m[y+clears] = self.m[y];
             }
-            if self.m[y] == (1 << 10) - 1 {
+            if self.m[y] == self.full_row() {
                 props.clears += 1 << y;
                 clears += 1;
             }
@@ -169,18 +316,20 @@ impl Field {
                 self.m[y] = 0;
             }
         }
-        // Calc attacks 
+        // Calc attacks, combos past the tables (narrow boards) staying at their last column
+        let combo: usize = (props.combo as usize).min(9);
         let atk: u8 = if clears < 4 && !mov.tspin {
             match clears {
                 0 => 0,
-                1 => [0, 0, 1, 1, 1, 1, 2, 2, 2, 2][props.combo as usize],
-                2 => [1, 1, 1, 1, 2, 2, 2, 2, 3, 3][props.combo as usize],
-                3 => [2, 2, 3, 3, 4, 4, 5, 5, 6, 6][props.combo as usize],
+                1 => [0, 0, 1, 1, 1, 1, 2, 2, 2, 2][combo],
+                2 => [1, 1, 1, 1, 2, 2, 2, 2, 3, 3][combo],
+                3 => [2, 2, 3, 3, 4, 4, 5, 5, 6, 6][combo],
                 _ => 0
             }
         } else if clears > 0 {
             let t = if mov.tspin {clears} else {0};
-            B2B_TABLE[props.b2b as usize][t][props.combo as usize] as u8
+            // Chains past the table's last row keep its bonus
+            B2B_TABLE[props.b2b.min(3) as usize][t][combo] as u8
         } else {0};
 
         // Setting attacks & ds (clears)
@@ -188,15 +337,48 @@ impl Field {
         props.ds = clears as u8;
 
         // If perfect clear
-        if clears > 0 && self.m.iter().sum::<u16>() == 0 {
+        if clears > 0 && self.m.iter().all(|&row| row == 0) {
             props.atk += 10;
         }
 
         // Combo
-        props.combo = if clears > 0 {props.combo + 1}  else {0};
+        props.combo = if clears > 0 {props.combo.saturating_add(1)}  else {0};
         
         // b2b
-        props.b2b = if (mov.tspin && clears > 0) || clears == 4 {props.b2b + 1} else {0};
+        props.b2b = if (mov.tspin && clears > 0) || clears == 4 {props.b2b.saturating_add(1)} else {0};
+    }
+
+    /// Pushes `lines` garbage rows with a hole at column `hole` into the bottom of the field.
+    /// Holes past the right edge are moved to the last column.
+    ///
+    /// Returns whether filled cells were pushed off the top of the buffer (top-out).
+    pub fn push_garbage (&mut self, lines: usize, hole: usize) -> bool {
+        let hole = hole.min(self.width as usize - 1);
+        let top = self.ceiling();
+        let lines = lines.min(FIELD_H - top);
+        let top_out = self.m[top..top + lines].iter().any(|&row| row != 0);
+        self.m.copy_within(top + lines.., top);
+        let row = self.full_row() ^ (1 << hole);
+        self.m[FIELD_H - lines..].fill(row);
+        top_out
+    }
+
+    /// Inserts `lines` garbage rows at the bottom of the field.
+    ///
+    /// The first row, topmost once inserted, has its hole at column `hole`, or the last column if
+    /// past the right edge. Each further row moves the hole to another random column with probability `messiness`:
+    /// 0 gives one clean column, 1 a new column every row. Returns whether filled cells were
+    /// pushed off the top (top-out).
+    pub fn insert_garbage (&mut self, lines: usize, hole: usize, messiness: f32, rng: &mut Rng) -> bool {
+        let mut hole = hole.min(self.width as usize - 1);
+        let mut top_out = false;
+        for i in 0..lines {
+            if i > 0 && rng.chance(messiness) {
+                hole = (hole + 1 + rng.below(self.width as usize - 1)) % self.width as usize;
+            }
+            top_out |= self.push_garbage(1, hole);
+        }
+        top_out
     }
 }
 
@@ -298,7 +480,7 @@ mod test {
         let field: Field = Field::new();
         let mut mov: Move = Move::new();
         let p: Piece = Piece::L;
-        let mut cache: ConflictCache = [[0; 20]; 4]; 
+        let mut cache: ConflictCache = [[0; FIELD_H]; 4]; 
 
         mov.y = 19;
         mov.x = 0;
@@ -309,7 +491,7 @@ mod test {
     fn field_conflict_map_test () {
          
         let mut field: Field = Field::new();
-        field.m = [
+        field.m[BUFFER_H..].copy_from_slice(&[
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -330,14 +512,14 @@ mod test {
             0b0_0_0_0_0_0_0_0_0_0,
             0b1_1_1_1_1_0_0_0_0_0,
             0b1_1_1_1_1_1_1_0_0_0,
-        ];
+        ]);
         println!("Field:\n{}", field);
 
-        let mut cache: ConflictCache = [[0; 20]; 4]; 
+        let mut cache: ConflictCache = [[0; FIELD_H]; 4]; 
 
         for r in 0..4 {
             println!("orientation: {r}\n");
-            for y in 0..20 {
+            for y in BUFFER_H as i8..FIELD_H as i8 {
                 for x in 0..10 {
                     let m = Move {
                         x,
@@ -363,7 +545,7 @@ mod test {
     #[test] 
     fn field_apply_move_test () {
         let mut field: Field = Field::new();
-        field.m = [
+        field.m[BUFFER_H..].copy_from_slice(&[
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -384,13 +566,13 @@ mod test {
             0b0_0_0_0_0_0_0_0_0_0,
             0b1_1_1_1_1_0_0_0_0_0,
             0b1_1_1_1_1_1_1_0_0_0,
-        ];
+        ]);
 
 
         let mut mov: Move = Move::new();
         let p: Piece = Piece::L;
         let h: Piece = Piece::L;
-        let mut cache: (ConflictCache, ConflictCache) = ([[0; 20]; 4], [[0; 20]; 4]);
+        let mut cache: (ConflictCache, ConflictCache) = ([[0; FIELD_H]; 4], [[0; FIELD_H]; 4]);
 
         mov.apply_key(&Key::Cw, &mut cache, &field, &p, &h);
         mov.apply_key(&Key::Left, &mut cache, &field, &p, &h);
@@ -410,13 +592,36 @@ mod test {
         //assert_eq!(field.m[19], 0b00001_10000);
     }
 
+    #[test]
+    fn field_parse_test () {
+        let mut field: Field = Field::new();
+        field.m[37] = 0b0_0_0_0_1_1_0_0_0_0;
+        field.m[38] = 0b0_0_0_0_0_1_1_1_1_1;
+        field.m[39] = 0b0_1_1_1_1_1_1_1_1_1;
+
+        assert_eq!(field.to_string().parse::<Field>(), Ok(field.clone()));
+
+        // Short, spaceless diagrams sit on the bottom of the board
+        let diagram = "
+            ....##....
+            #####.....
+            #########.
+        ";
+        assert_eq!(diagram.parse::<Field>(), Ok(field));
+
+        assert_eq!("###".parse::<Field>(), Err(ParseError::Row(0)));
+        assert_eq!("##########\n#########".parse::<Field>(), Err(ParseError::Row(1)));
+        assert_eq!("..........\n###########".parse::<Field>(), Err(ParseError::Row(1)));
+        assert_eq!("..........\n".repeat(41).parse::<Field>(), Err(ParseError::Height));
+    }
+
     #[test] 
     fn field_set_props_test () {
         let mut m = Move::new();
         let mut props = Props::new();
         let mut field = Field::new();
         
-        field.m = [
+        field.m[BUFFER_H..].copy_from_slice(&[
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -437,8 +642,8 @@ mod test {
             0b0_0_0_0_0_0_0_0_0_0,
             0b1_1_1_1_1_0_0_0_0_0,
             0b1_1_1_1_1_1_1_0_0_0,
-        ];
-        let cache: ConflictCache = [[0; 20]; 4]; 
+        ]);
+        let cache: ConflictCache = [[0; FIELD_H]; 4]; 
 
         m.apply_key(&Key::HardDrop, &mut (cache, cache), &field, &Piece::L, &Piece::L);
 
@@ -448,7 +653,93 @@ mod test {
         println!("{}", field);
 
         assert_eq!(props.ds, 2);
-        assert_eq!(field.m[18], 0);
-        assert_eq!(field.m[19], 0);
+        assert_eq!(field.m[38], 0);
+        assert_eq!(field.m[39], 0);
+    }
+
+    #[test]
+    fn field_insert_garbage_test () {
+        let mut rng = Rng::new(7);
+
+        // Clean garbage keeps a single column
+        let mut field = Field::new();
+        field.m[39] = 0b0_0_0_0_0_0_0_0_1_1;
+        assert!(!field.insert_garbage(3, 4, 0.0, &mut rng));
+        println!("{}", field);
+        assert_eq!(field.m[36], 0b0_0_0_0_0_0_0_0_1_1);
+        assert!(field.m[37..].iter().all(|&row| row == ((1 << 10) - 1) ^ (1 << 4)));
+
+        // Full messiness moves the hole every row
+        let mut field = Field::new();
+        assert!(!field.insert_garbage(8, 0, 1.0, &mut rng));
+        println!("{}", field);
+        assert_eq!(field.m[32], ((1 << 10) - 1) ^ 1);
+        for y in 32..39 {
+            assert_eq!(field.m[y].count_ones(), 9);
+            assert_ne!(field.m[y], field.m[y + 1]);
+        }
+
+        // Same seed, same garbage
+        let (mut a, mut b) = (Field::new(), Field::new());
+        a.insert_garbage(10, 2, 0.5, &mut Rng::new(1));
+        b.insert_garbage(10, 2, 0.5, &mut Rng::new(1));
+        assert_eq!(a, b);
+
+        // Filled cells pushed above row 0 top out
+        let mut field = Field::new();
+        field.m[2] = 1;
+        assert!(!field.insert_garbage(2, 0, 0.0, &mut rng));
+        assert!(field.insert_garbage(1, 0, 0.0, &mut rng));
+
+        // Holes past the right edge stay on the board
+        let mut field = Field::new();
+        assert!(!field.insert_garbage(2, 12, 0.0, &mut rng));
+        assert!(!field.push_garbage(1, 16));
+        assert!(!field.push_garbage(1, usize::MAX));
+        assert!(field.m[36..].iter().all(|&row| row == ((1 << 10) - 1) ^ (1 << 9)));
+        let mut field = Field::with_size(MIN_W, VISIBLE_H);
+        assert!(!field.push_garbage(1, 9));
+        assert_eq!(field.m[39], 0b0_1_1_1);
+    }
+
+    #[test]
+    fn field_size_test () {
+        // 4-wide boards are parsed from their diagram
+        let mut field: Field = "
+            #..#
+            #..#
+        ".parse().unwrap();
+        println!("{}", field);
+        assert_eq!((field.width, field.full_row()), (4, 0b1_1_1_1));
+        assert_eq!(field.spawn().x, 1);
+
+        let mut cache: ConflictCache = [[0; FIELD_H]; 4];
+        let o = Move { x: 3, y: 30, ..Move::new() };
+        assert!(field.check_conflict(&mut cache, &o, &Piece::O));
+        let o = Move { x: 1, y: 39, ..Move::new() };
+        assert!(!field.check_conflict(&mut cache, &o, &Piece::O));
+
+        // Long combos stay at the end of the attack tables
+        let mut props = Props::new();
+        props.combo = 20;
+        field = field.apply_move(&o, &Piece::O, &Piece::None).unwrap();
+        field.set_props(&o, &mut props);
+        assert_eq!((props.ds, props.atk, props.combo), (2, 13, 21));
+
+        // Pieces past the right edge of a 16-wide board conflict
+        let field = Field::with_size(MAX_W, VISIBLE_H);
+        let mut cache: ConflictCache = [[0; FIELD_H]; 4];
+        assert!(field.check_conflict(&mut cache, &Move { x: 15, y: 30, ..Move::new() }, &Piece::O));
+        assert!(!field.check_conflict(&mut cache, &Move { x: 14, y: 30, ..Move::new() }, &Piece::O));
+
+        // Short boards keep their buffer right above the visible rows
+        let mut field = Field::with_size(FIELD_W, 4);
+        assert_eq!((field.ceiling(), field.visible_top(), field.spawn().y), (16, 36, 35));
+        let mut cache: ConflictCache = [[0; FIELD_H]; 4];
+        assert!(field.check_conflict(&mut cache, &Move { y: 16, ..Move::new() }, &Piece::O));
+        assert!(!field.check_conflict(&mut cache, &Move { y: 17, ..Move::new() }, &Piece::O));
+        field.m[17] = 1;
+        assert!(!field.push_garbage(1, 0));
+        assert!(field.push_garbage(1, 0));
     }
 }
@@ -0,0 +1,753 @@
+//! Module implementing fumen (v115) import & export.
+//!
+//! Fumen is the board-sharing format of harddrop.com / knewjade's `tetris-fumen`.
+//! Fumen boards are 23 rows tall plus a garbage row. They map onto the bottom rows of a `Field`;
+//! higher buffer rows are dropped on export.
+//! Colors are dropped on import. On export, cells left by the previous page keep their color,
+//! others are gray. Only 10-wide fields can be exported.
+
+use std::fmt;
+
+use super::{Field, Move, Piece, State};
+use crate::field::{PIECE_MAP, FIELD_H};
+
+const ENCODE_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_BASE: u32 = 96;
+const PREFIX: &str = "v115@";
+
+/// Width of the fumen board.
+const W: usize = 10;
+/// Height of the fumen board, above the garbage row.
+const TOP: usize = 23;
+/// Cells in the fumen board, including the garbage row.
+const BLOCKS: usize = (TOP + 1) * W;
+/// `Field` rows above the top of the fumen board.
+const OFFSET: usize = FIELD_H - TOP;
+
+/// Error raised when decoding an invalid fumen.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FumenError {
+    /// Missing or unsupported version prefix.
+    Version,
+    /// Character outside of the fumen alphabet.
+    Char(char),
+    /// Data ended in the middle of a page.
+    Truncated,
+    /// Field data overflowing the board.
+    Field,
+    /// Field of another width than the fumen board.
+    Width(u8),
+}
+
+impl fmt::Display for FumenError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FumenError::Version => write!(f, "unsupported fumen version, expected v115"),
+            FumenError::Char(c) => write!(f, "invalid fumen character '{}'", c),
+            FumenError::Truncated => write!(f, "fumen data ended unexpectedly"),
+            FumenError::Field => write!(f, "fumen field data overflows the board"),
+            FumenError::Width(w) => write!(f, "fumen boards are {} wide, not {}", W, w),
+        }
+    }
+}
+impl std::error::Error for FumenError {}
+
+/// A single fumen page.
+#[derive(Clone, Debug)]
+pub struct Page {
+    /// Board shown on the page, before the piece is placed.
+    pub field: Field,
+    /// Piece placed on the page, if any.
+    pub piece: Option<(Piece, Move)>,
+    /// Comment of the page, if set on this page.
+    pub comment: Option<String>,
+}
+
+/// Fumen piece & rotation numbering.
+fn fumen_piece (p: Piece) -> u32 {
+    match p {
+        Piece::I => 1,
+        Piece::L => 2,
+        Piece::O => 3,
+        Piece::Z => 4,
+        Piece::T => 5,
+        Piece::J => 6,
+        Piece::S => 7,
+        Piece::None => 0,
+    }
+}
+fn tetron_piece (v: u32) -> Piece {
+    match v {
+        1 => Piece::I,
+        2 => Piece::L,
+        3 => Piece::O,
+        4 => Piece::Z,
+        5 => Piece::T,
+        6 => Piece::J,
+        7 => Piece::S,
+        _ => Piece::None,
+    }
+}
+// Fumen encodes rotations as reverse, right, spawn, left.
+fn fumen_rotation (r: u8) -> u32 {
+    [2, 1, 0, 3][r as usize % 4]
+}
+fn tetron_rotation (v: u32) -> u8 {
+    [2, 1, 0, 3][v as usize % 4]
+}
+
+/// Cells of a placed piece in `Field` coordinates (`y` downwards).
+fn move_cells (p: Piece, x: i8, y: i8, r: u8) -> Vec<(i8, i8)> {
+    let n: i8 = if p == Piece::I {5} else {3};
+    let map = &PIECE_MAP[p as usize][r as usize];
+    let mut cells = vec![];
+    for j in 0..n {
+        let bitseg: u16 = map[j as usize].reverse_bits() >> (16 - n);
+        for b in 0..n {
+            if bitseg & (1 << b) > 0 {
+                cells.push((x - n/2 + b, y - n/2 + j));
+            }
+        }
+    }
+    cells.sort();
+    cells
+}
+
+/// Cells of a placed piece in fumen coordinates (`y` upwards, 0 = bottom row).
+///
+/// `(x, y)` is the corrected fumen center, as used by `tetris-fumen`.
+fn fumen_cells (p: Piece, x: i32, y: i32, rotation: u32) -> Vec<(i32, i32)> {
+    let blocks: [(i32, i32); 4] = match p {
+        Piece::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        Piece::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        Piece::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Piece::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        Piece::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        Piece::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        Piece::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        Piece::None => return vec![],
+    };
+    let mut cells: Vec<(i32, i32)> = blocks.iter().map(|&(bx, by)| {
+        let (bx, by) = match rotation {
+            0 => (-bx, -by),
+            1 => (by, -bx),
+            3 => (-by, bx),
+            _ => (bx, by),
+        };
+        (x + bx, y + by)
+    }).collect();
+    cells.sort();
+    cells
+}
+
+/// Correction between the stored fumen coordinate and the center used by `fumen_cells`.
+fn center_correction (p: Piece, rotation: u32) -> (i32, i32) {
+    match (p, rotation) {
+        (Piece::O, 3) => (1, -1),
+        (Piece::O, 0) => (1, 0),
+        (Piece::O, 2) => (0, -1),
+        (Piece::I, 0) => (1, 0),
+        (Piece::I, 3) => (0, -1),
+        (Piece::S, 2) => (0, -1),
+        (Piece::S, 1) => (-1, 0),
+        (Piece::Z, 2) => (0, -1),
+        (Piece::Z, 3) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+/// Converts a tetron placement to the (corrected) fumen center.
+fn to_fumen_center (p: Piece, mov: &Move) -> (i32, i32) {
+    let mut target: Vec<(i32, i32)> = move_cells(p, mov.x, mov.y, mov.r).iter()
+        .map(|&(x, y)| (x as i32, FIELD_H as i32 - 1 - y as i32))
+        .collect();
+    target.sort();
+    let rotation = fumen_rotation(mov.r);
+    for &(x, y) in target.iter() {
+        if fumen_cells(p, x, y, rotation) == target {
+            return (x, y);
+        }
+    }
+    unreachable!("fumen and tetron piece shapes disagree");
+}
+
+/// Converts a fumen placement (corrected center) to a tetron `Move`.
+fn to_move (p: Piece, x: i32, y: i32, rotation: u32) -> Move {
+    let r = tetron_rotation(rotation);
+    let mut target: Vec<(i8, i8)> = fumen_cells(p, x, y, rotation).iter()
+        .map(|&(x, y)| (x as i8, (FIELD_H as i32 - 1 - y) as i8))
+        .collect();
+    target.sort();
+    let (tx, ty) = target[0];
+    for dy in -2..=2 {
+        for dx in -2..=2 {
+            if move_cells(p, tx + dx, ty + dy, r) == target {
+                return Move { x: tx + dx, y: ty + dy, r, lock: true, ..Move::new() };
+            }
+        }
+    }
+    unreachable!("fumen and tetron piece shapes disagree");
+}
+
+/// Fumen board, including the garbage row.
+///
+/// Indexed from the top: row `TOP` is the garbage row.
+#[derive(Clone, PartialEq, Eq)]
+struct Board([u8; BLOCKS]);
+
+impl Board {
+    fn from_field (field: &Field) -> Result<Self, FumenError> {
+        if field.width as usize != W {
+            return Err(FumenError::Width(field.width));
+        }
+        let mut cells = [0; BLOCKS];
+        for y in 0..TOP {
+            for x in 0..W {
+                if field.m[y + OFFSET] & (1 << x) > 0 {
+                    cells[y * W + x] = 8;
+                }
+            }
+        }
+        Ok(Self(cells))
+    }
+
+    fn to_field (&self) -> Field {
+        let mut field = Field::new();
+        for y in 0..TOP {
+            for x in 0..W {
+                if self.0[y * W + x] > 0 {
+                    field.m[y + OFFSET] |= 1 << x;
+                }
+            }
+        }
+        field
+    }
+
+    /// Places a piece given its fumen center.
+    fn put (&mut self, p: Piece, x: i32, y: i32, rotation: u32) {
+        for (cx, cy) in fumen_cells(p, x, y, rotation) {
+            if (0..W as i32).contains(&cx) && (0..TOP as i32).contains(&cy) {
+                self.0[(TOP - 1 - cy as usize) * W + cx as usize] = fumen_piece(p) as u8;
+            }
+        }
+    }
+
+    fn clear_lines (&mut self) {
+        let mut rows: Vec<[u8; W]> = (0..TOP)
+            .map(|y| self.0[y * W..(y + 1) * W].try_into().unwrap())
+            .filter(|row: &[u8; W]| row.contains(&0))
+            .collect();
+        while rows.len() < TOP {
+            rows.insert(0, [0; W]);
+        }
+        for (y, row) in rows.iter().enumerate() {
+            self.0[y * W..(y + 1) * W].copy_from_slice(row);
+        }
+    }
+
+    fn rise_garbage (&mut self) {
+        self.0.copy_within(W.., 0);
+        self.0[TOP * W..].fill(0);
+    }
+
+    fn mirror (&mut self) {
+        for y in 0..TOP {
+            self.0[y * W..(y + 1) * W].reverse();
+        }
+    }
+
+    /// Applies the lock step of a page to obtain the next page's board.
+    fn lock (&mut self, action: &Action) {
+        if let Some((p, x, y, rotation)) = action.piece {
+            self.put(p, x, y, rotation);
+        }
+        self.clear_lines();
+        if action.rise {
+            self.rise_garbage();
+        }
+        if action.mirror {
+            self.mirror();
+        }
+    }
+}
+
+/// Page action flags & operation. Piece position is the corrected fumen center.
+struct Action {
+    piece: Option<(Piece, i32, i32, u32)>,
+    rise: bool,
+    mirror: bool,
+    colorize: bool,
+    comment: bool,
+    lock: bool,
+}
+
+impl Action {
+    fn decode (mut v: u32) -> Self {
+        let p = tetron_piece(v % 8);
+        v /= 8;
+        let rotation = v % 4;
+        v /= 4;
+        let coord = v % BLOCKS as u32;
+        v /= BLOCKS as u32;
+        let flag = |v: &mut u32| { let b = *v % 2 == 1; *v /= 2; b };
+        let rise = flag(&mut v);
+        let mirror = flag(&mut v);
+        let colorize = flag(&mut v);
+        let comment = flag(&mut v);
+        let lock = !flag(&mut v);
+
+        let piece = if p == Piece::None { None } else {
+            let (dx, dy) = center_correction(p, rotation);
+            let x = (coord % W as u32) as i32 + dx;
+            let y = TOP as i32 - 1 - (coord / W as u32) as i32 + dy;
+            Some((p, x, y, rotation))
+        };
+        Self { piece, rise, mirror, colorize, comment, lock }
+    }
+
+    fn encode (&self) -> u32 {
+        let mut v: u32 = if self.lock {0} else {1};
+        for flag in [self.comment, self.colorize, self.mirror, self.rise] {
+            v = v * 2 + flag as u32;
+        }
+        let (p, coord, rotation) = match self.piece {
+            Some((p, x, y, rotation)) => {
+                let (dx, dy) = center_correction(p, rotation);
+                (p, (TOP as i32 - 1 - (y - dy)) as u32 * W as u32 + (x - dx) as u32, rotation)
+            },
+            None => (Piece::None, 0, 0),
+        };
+        v = v * BLOCKS as u32 + coord;
+        v = v * 4 + rotation;
+        v * 8 + fumen_piece(p)
+    }
+}
+
+/// Base-64 value stream.
+struct Reader {
+    values: Vec<u32>,
+    pos: usize,
+}
+
+impl Reader {
+    fn poll (&mut self, n: usize) -> Result<u32, FumenError> {
+        if self.pos + n > self.values.len() {
+            return Err(FumenError::Truncated);
+        }
+        let v = self.values[self.pos..self.pos + n].iter().rev().fold(0, |acc, &d| acc * 64 + d);
+        self.pos += n;
+        Ok(v)
+    }
+
+    fn is_empty (&self) -> bool {
+        self.pos >= self.values.len()
+    }
+}
+
+/// Writes `n` base-64 digits of `v`, least significant first.
+fn push (out: &mut Vec<u32>, mut v: u32, n: usize) {
+    for _ in 0..n {
+        out.push(v % 64);
+        v /= 64;
+    }
+}
+
+/// JavaScript's `escape()`, which fumen applies to comments.
+fn escape (s: &str) -> String {
+    let mut out = String::new();
+    for unit in s.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => out.push(c),
+            _ if unit < 256 => out.push_str(&format!("%{:02X}", unit)),
+            _ => out.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    out
+}
+
+/// JavaScript's `unescape()`.
+fn unescape (s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut units: Vec<u16> = vec![];
+    let hex = |from: usize, len: usize| -> Option<u16> {
+        let digits: String = chars.get(from..from + len)?.iter().collect();
+        u16::from_str_radix(&digits, 16).ok()
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '%' {
+            if chars.get(i + 1) == Some(&'u') {
+                if let Some(u) = hex(i + 2, 4) {
+                    units.push(u);
+                    i += 6;
+                    continue;
+                }
+            } else if let Some(u) = hex(i + 1, 2) {
+                units.push(u);
+                i += 3;
+                continue;
+            }
+        }
+        let mut buf = [0; 2];
+        units.extend_from_slice(chars[i].encode_utf16(&mut buf));
+        i += 1;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Decodes all pages of a fumen string.
+pub fn decode (data: &str) -> Result<Vec<Page>, FumenError> {
+    let data = data.trim();
+    let body = data.strip_prefix(PREFIX)
+        .or_else(|| data.split_once(PREFIX).map(|(_, b)| b))
+        .ok_or(FumenError::Version)?;
+    let values = body.chars()
+        .filter(|&c| c != '?')
+        .map(|c| ENCODE_TABLE.iter().position(|&e| e as char == c).map(|v| v as u32).ok_or(FumenError::Char(c)))
+        .collect::<Result<Vec<u32>, FumenError>>()?;
+    let mut reader = Reader { values, pos: 0 };
+
+    let mut pages: Vec<Page> = vec![];
+    let mut prev = Board([0; BLOCKS]);
+    let mut repeat: u32 = 0;
+    while !reader.is_empty() {
+        // Field, as a run-length encoded diff on the previous page.
+        let mut board = prev.clone();
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut index = 0;
+            let mut changed = true;
+            while index < BLOCKS {
+                let v = reader.poll(2)?;
+                let diff = (v / BLOCKS as u32) as i32 - 8;
+                let count = (v % BLOCKS as u32) as usize + 1;
+                if diff == 0 && count == BLOCKS {
+                    changed = false;
+                }
+                if index + count > BLOCKS {
+                    return Err(FumenError::Field);
+                }
+                for cell in board.0[index..index + count].iter_mut() {
+                    *cell = (*cell as i32 + diff).clamp(0, 8) as u8;
+                }
+                index += count;
+            }
+            if !changed {
+                repeat = reader.poll(1)?;
+            }
+        }
+
+        let action = Action::decode(reader.poll(3)?);
+        let comment = if action.comment {
+            let len = reader.poll(2)? as usize;
+            let mut text = String::new();
+            for _ in 0..len.div_ceil(4) {
+                let mut v = reader.poll(5)?;
+                for _ in 0..4 {
+                    text.push((b' ' + (v % COMMENT_BASE) as u8) as char);
+                    v /= COMMENT_BASE;
+                }
+            }
+            text.truncate(len);
+            Some(unescape(&text))
+        } else {
+            None
+        };
+
+        pages.push(Page {
+            field: board.to_field(),
+            piece: action.piece.map(|(p, x, y, rotation)| (p, to_move(p, x, y, rotation))),
+            comment,
+        });
+
+        if action.lock {
+            board.lock(&action);
+        }
+        prev = board;
+    }
+    Ok(pages)
+}
+
+/// Encodes pages into a fumen string.
+///
+/// Each page's field is stored as a diff on the board left by the previous page's placement,
+/// so consecutive placements encode compactly. Errs on fields that are not 10 wide.
+pub fn encode (pages: &[Page]) -> Result<String, FumenError> {
+    let mut values: Vec<u32> = vec![];
+    let mut prev = Board([0; BLOCKS]);
+    let mut prev_comment: Option<&str> = None;
+    let mut repeat_index: Option<usize> = None;
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut board = Board::from_field(&page.field)?;
+        // Keep the garbage row, out of the `Field`'s reach, from the previous page.
+        board.0[TOP * W..].copy_from_slice(&prev.0[TOP * W..]);
+        // Cells still filled keep their color, so pages continuing the previous one add no diff.
+        for (cell, prev) in board.0.iter_mut().zip(prev.0.iter()) {
+            if *cell > 0 && *prev > 0 {
+                *cell = *prev;
+            }
+        }
+
+        // Field diff
+        {
+            let diffs: Vec<u32> = (0..BLOCKS).map(|j| (board.0[j] as i32 - prev.0[j] as i32 + 8) as u32).collect();
+            let mut runs: Vec<(u32, u32)> = vec![];
+            for &d in diffs.iter() {
+                match runs.last_mut() {
+                    Some((pd, count)) if *pd == d => *count += 1,
+                    _ => runs.push((d, 0)),
+                }
+            }
+            if runs.len() > 1 || runs[0].0 != 8 {
+                for (d, count) in runs {
+                    push(&mut values, d * BLOCKS as u32 + count, 2);
+                }
+                repeat_index = None;
+            } else {
+                match repeat_index {
+                    Some(j) if values[j] < 63 => values[j] += 1,
+                    _ => {
+                        push(&mut values, 8 * BLOCKS as u32 + BLOCKS as u32 - 1, 2);
+                        values.push(0);
+                        repeat_index = Some(values.len() - 1);
+                    }
+                }
+            }
+        }
+
+        // Action & comment
+        let comment = page.comment.as_deref()
+            .filter(|c| prev_comment != Some(c) && (i > 0 || !c.is_empty()));
+        let action = Action {
+            piece: page.piece.as_ref().map(|(p, mov)| {
+                let (x, y) = to_fumen_center(*p, mov);
+                (*p, x, y, fumen_rotation(mov.r))
+            }),
+            rise: false,
+            mirror: false,
+            colorize: true,
+            comment: comment.is_some(),
+            lock: true,
+        };
+        push(&mut values, action.encode(), 3);
+        if let Some(comment) = comment {
+            let escaped: Vec<u8> = escape(comment).into_bytes();
+            let len = escaped.len().min(4095);
+            push(&mut values, len as u32, 2);
+            for chunk in escaped[..len].chunks(4) {
+                let v = chunk.iter().rev().fold(0, |acc, &c| acc * COMMENT_BASE + (c - b' ') as u32);
+                push(&mut values, v, 5);
+            }
+            prev_comment = Some(comment);
+        }
+
+        board.lock(&action);
+        prev = board;
+    }
+
+    let data: String = values.iter().map(|&v| ENCODE_TABLE[v as usize] as char).collect();
+    if data.len() <= 42 {
+        return Ok(format!("{}{}", PREFIX, data));
+    }
+    // Break the data up with '?', as the reference encoder does.
+    let mut out = format!("{}{}", PREFIX, &data[..42]);
+    for chunk in data.as_bytes()[42..].chunks(47) {
+        out.push('?');
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+    }
+    Ok(out)
+}
+
+/// Encodes a `solve()` result: the placement on `state`, followed by the board it leaves.
+///
+/// The score is written as the comment of the first page.
+pub fn encode_result (state: &State, result: &(State, Move, f32)) -> Result<String, FumenError> {
+    encode_line(state, std::slice::from_ref(result))
+}
+
+/// Encodes a `solve_pv()` line: a page per placement from `state`, followed by the board it leaves.
+///
+/// The score of every ply is written as the comment of its page.
+pub fn encode_line (state: &State, line: &[(State, Move, f32)]) -> Result<String, FumenError> {
+    let mut pages: Vec<Page> = Vec::with_capacity(line.len() + 1);
+    let mut prev = state;
+    for (child, mov, score) in line.iter() {
+        pages.push(Page {
+            field: prev.field.clone(),
+            piece: Some((prev.placed_piece(mov), mov.clone())),
+            comment: Some(format!("score: {}", score)),
+        });
+        prev = child;
+    }
+    pages.push(Page {
+        field: prev.field.clone(),
+        piece: None,
+        comment: None,
+    });
+    encode(&pages)
+}
+
+impl Field {
+    /// Decodes the board of the first page of a fumen.
+    pub fn from_fumen (data: &str) -> Result<Field, FumenError> {
+        let pages = decode(data)?;
+        Ok(pages.into_iter().next().map_or_else(Field::new, |p| p.field))
+    }
+
+    /// Encodes the field as a single page fumen. Errs if the field is not 10 wide.
+    pub fn to_fumen (&self) -> Result<String, FumenError> {
+        encode(&[Page { field: self.clone(), piece: None, comment: None }])
+    }
+}
+
+impl State {
+    /// Decodes a fumen into a `State`.
+    ///
+    /// The board is the one of the first page; the queue is the sequence of pieces placed
+    /// across the pages.
+    pub fn from_fumen (data: &str) -> Result<State, FumenError> {
+        let pages = decode(data)?;
+        let mut state = State::new();
+        if let Some(page) = pages.first() {
+            state.field = page.field.clone();
+        }
+        state.pieces.extend(pages.iter().filter_map(|p| p.piece.as_ref().map(|(p, _)| *p)));
+        state.infer_bag();
+        Ok(state)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{solve, EvaluatorMode};
+    use crate::config::Config;
+
+    #[test]
+    fn fumen_empty_test () {
+        assert_eq!(Field::new().to_fumen(), Ok("v115@vhAAgH".to_string()));
+        assert_eq!(Field::with_size(12, 20).to_fumen(), Err(FumenError::Width(12)));
+
+        let pages = decode("v115@vhAAgH").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].field, Field::new());
+        assert!(pages[0].piece.is_none());
+
+        assert_eq!(decode("v114@vhAAgH").unwrap_err(), FumenError::Version);
+        assert_eq!(decode("v115@vh!AgH").unwrap_err(), FumenError::Char('!'));
+        assert_eq!(decode("v115@vhAAg").unwrap_err(), FumenError::Truncated);
+    }
+
+    #[test]
+    fn fumen_round_trip_test () {
+        let mut field = Field::new();
+        field.m[37] = 0b0_0_0_0_1_1_0_0_0_0;
+        field.m[38] = 0b0_0_0_0_0_1_1_1_1_1;
+        field.m[39] = 0b0_1_1_1_1_1_1_1_1_1;
+
+        // Every piece & rotation survives a trip through fumen coordinates.
+        let mut pages = vec![];
+        for p in [Piece::I, Piece::O, Piece::T, Piece::L, Piece::J, Piece::S, Piece::Z] {
+            for r in 0..4 {
+                let mov = Move { x: 4, y: 25, r, lock: true, ..Move::new() };
+                pages.push(Page { field: field.clone(), piece: Some((p, mov)), comment: None });
+            }
+        }
+        pages[0].comment = Some("T-spin? 100%".to_string());
+        let data = encode(&pages).unwrap();
+        println!("{}", data);
+
+        let decoded = decode(&data).unwrap();
+        assert_eq!(decoded.len(), pages.len());
+        for (a, b) in pages.iter().zip(decoded.iter()) {
+            assert_eq!(a.field, b.field);
+            let ((pa, ma), (pb, mb)) = (a.piece.as_ref().unwrap(), b.piece.as_ref().unwrap());
+            assert_eq!(pa, pb);
+            assert_eq!(move_cells(*pa, ma.x, ma.y, ma.r), move_cells(*pb, mb.x, mb.y, mb.r));
+        }
+        assert_eq!(decoded[0].comment.as_deref(), Some("T-spin? 100%"));
+    }
+
+    #[test]
+    fn fumen_sequence_test () {
+        // Placements lock & clear lines between pages, like in the reference viewer.
+        let mut state = State::new();
+        state.pieces.extend([Piece::I, Piece::O, Piece::T]);
+        state.field.m[39] = 0b0_0_0_0_1_1_1_1_1_1;
+
+        let out = solve(&state, &Config::new(0, EvaluatorMode::Norm)).unwrap();
+        let data = encode_result(&state, &out).unwrap();
+        println!("{}", data);
+
+        let pages = decode(&data).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].field, state.field);
+        assert_eq!(pages[1].field, out.0.field);
+        assert_eq!(pages[0].comment, Some(format!("score: {}", out.2)));
+
+        let decoded = State::from_fumen(&data).unwrap();
+        assert_eq!(decoded.field, state.field);
+        assert_eq!(decoded.pieces.len(), 1);
+    }
+
+    /// Piece cells & comment of a page.
+    type PageSummary = (Option<(Piece, Vec<(i8, i8)>)>, Option<String>);
+
+    /// Pieces & comments of `pages`, for comparison with fixtures.
+    fn summary (pages: &[Page]) -> Vec<PageSummary> {
+        pages.iter().map(|page| (
+            page.piece.as_ref().map(|(p, m)| (*p, move_cells(*p, m.x, m.y, m.r))),
+            page.comment.clone(),
+        )).collect()
+    }
+
+    #[test]
+    fn fumen_fixture_test () {
+        // Fixtures built to the v115 format of knewjade's `tetris-fumen`.
+        // Opener spread over pages, each on the board left by the previous page, clearing a line
+        let data = "v115@vhExOJTLJyRJ5IJAgH";
+        let pages = decode(data).unwrap();
+        assert_eq!(summary(&pages), [
+            (Some((Piece::I, vec![(0, 39), (1, 39), (2, 39), (3, 39)])), None),
+            (Some((Piece::O, vec![(4, 38), (4, 39), (5, 38), (5, 39)])), None),
+            (Some((Piece::L, vec![(6, 39), (7, 39), (8, 38), (8, 39)])), None),
+            (Some((Piece::I, vec![(9, 36), (9, 37), (9, 38), (9, 39)])), None),
+            (None, None),
+        ]);
+        assert_eq!(pages[0].field, Field::new());
+        assert_eq!(pages[4].field.m[36..], [0, 0b1_0_0_0_0_0_0_0_0_0, 0b1_0_0_0_0_0_0_0_0_0, 0b1_1_0_0_1_1_0_0_0_0]);
+        assert_eq!(encode(&pages), Ok(data.to_string()));
+
+        // Long data is split with '?', and comments are only stored when they change
+        let data = "v115@9gA8AeJ8AeJ8AeJ8AeE8JeFyXSAUE7QEJGVABGoo2A?RYcRAyfAAAvhC3mXEAuoneE8pIAgWDAF2aCA";
+        let pages = decode(data).unwrap();
+        assert_eq!(summary(&pages), [
+            (Some((Piece::T, vec![(3, 33), (4, 33), (4, 34), (5, 33)])), Some("T-spin? 100%".to_string())),
+            (Some((Piece::S, vec![(0, 32), (1, 31), (1, 32), (2, 31)])), Some("next".to_string())),
+            (Some((Piece::Z, vec![(7, 31), (7, 32), (8, 30), (8, 31)])), None),
+            (None, Some("end".to_string())),
+        ]);
+        assert_eq!(pages[0].field.m[36..], [
+            0b1_1_1_1_1_1_1_1_0_1,
+            0b1_1_1_1_1_1_1_0_1_1,
+            0b1_1_1_1_1_1_0_1_1_1,
+            0b1_1_1_1_1_0_1_1_1_1,
+        ]);
+        assert_eq!(encode(&pages), Ok(data.to_string()));
+
+        // Data of 41 & 42 characters is kept whole
+        let data = "v115@HhA8AeD8AeB8BeC8AeA8EeA8BeA8BeA8AeA8JeAgH";
+        let pages = decode(data).unwrap();
+        assert_eq!(pages[0].field.m[37..], [0b0_1_1_0_1_1_1_1_0_1, 0b0_0_0_0_1_0_1_1_1_0, 0b1_0_1_0_0_1_0_0_1_0]);
+        assert_eq!(encode(&pages), Ok(data.to_string()));
+        let mut field = pages[0].field.clone();
+        for row in 0..4 {
+            field.m[36] = row;
+            let data = field.to_fumen().unwrap();
+            assert_eq!(decode(&data).unwrap()[0].field, field);
+        }
+    }
+}
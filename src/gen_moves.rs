@@ -3,7 +3,7 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use super::{Field, Move, State, Key, Piece};
-use crate::field::ConflictCache;
+use crate::field::{ConflictCache, FIELD_H, MAX_W};
 
  
 /// Generates all valid Moves that can be applied to a given state. 
@@ -13,6 +13,11 @@ use crate::field::ConflictCache;
 /// Append only valid and unique moves into the BFS queue. 
 /// Uniqueness of Field is guarenteed via a Hashset<T>. This, in turn, guarentees uniqueness in Moves.
 pub fn gen_moves(state: &State) -> HashMap<Field, Move> {
+    gen_moves_counted(state).0
+}
+
+/// `gen_moves()`, also returning the number of piece conflicts computed.
+pub(crate) fn gen_moves_counted(state: &State) -> (HashMap<Field, Move>, u64) {
     let _bencher: Option<crate::Bencher> = if cfg!(feature = "bench") {
         unsafe {
             Some( crate::Bencher::new( &mut crate::BENCH_DATA.gen_moves ) )
@@ -21,28 +26,28 @@ pub fn gen_moves(state: &State) -> HashMap<Field, Move> {
 
     // Check if there is even a piece to expand on.
     if state.pieces.is_empty() {
-        return HashMap::new();
+        return (HashMap::new(), 0);
     }
     let piece: &Piece = &state.pieces[0];
-    let hold: &Piece = if state.hold == Piece::None { &state.pieces[1] } else { &state.hold };
+    let hold: &Piece = if state.hold == Piece::None { state.pieces.get(1).unwrap_or(&Piece::None) } else { &state.hold };
 
     let mut field_hash: HashMap<Field, Move> = HashMap::new();
     let mut move_hash: HashSet<u64> = HashSet::new();
     let mut q: VecDeque<Move> = VecDeque::new();
     q.reserve(40);
 
-    let mut cache: (ConflictCache, ConflictCache) = ([[0; 20]; 4], [[0; 20]; 4]);
+    let mut cache: (ConflictCache, ConflictCache) = ([[0; FIELD_H]; 4], [[0; FIELD_H]; 4]);
 
     // Base cases for BFS
     {
-        let m: Move = Move::new();
+        let m: Move = state.field.spawn();
         if !state.field.check_conflict(&mut cache.0, &m, piece) {
             q.push_back(m);
         }
     } 
     // Hold base case
     if *hold != Piece::None {
-        let mut m = Move::new();
+        let mut m = state.field.spawn();
         m.apply_key(&Key::Hold, &mut cache, &state.field, piece, hold);
         if !state.field.check_conflict(&mut cache.1, &m, piece) {
             q.push_back(m);
@@ -78,13 +83,19 @@ pub fn gen_moves(state: &State) -> HashMap<Field, Move> {
         }
     }
 
-    field_hash
+    // Computed conflicts are flagged in the upper bits of the caches
+    let conflicts: u32 = cache.0.iter().chain(cache.1.iter())
+        .flatten()
+        .map(|row| (row >> MAX_W).count_ones())
+        .sum();
+    (field_hash, conflicts as u64)
 } 
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::field::BUFFER_H;
 
     #[test]
     fn gen_moves_test () {
@@ -96,7 +107,7 @@ mod tests {
         state.pieces.push_back(Piece::S);
         state.hold = Piece::Z;
 
-        state.field.m = [   
+        state.field.m[BUFFER_H..].copy_from_slice(&[   
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -117,7 +128,7 @@ mod tests {
             0b0_0_0_0_0_0_0_0_0_0,
             0b1_1_1_1_1_0_0_0_0_0,
             0b1_1_1_1_1_1_1_0_0_0,
-        ];
+        ]);
 
     
         let map = gen_moves(&state);
@@ -125,4 +136,21 @@ mod tests {
             println!("{}", field);
         }
     }
+
+    #[test]
+    fn gen_moves_size_test () {
+        let state: State = "
+            queue: IT
+            #...
+            ##.#
+        ".parse().unwrap();
+        let map = gen_moves(&state);
+        for (field, _) in map.iter() {
+            println!("{}", field);
+            assert!(field.m.iter().all(|&row| row <= field.full_row()));
+        }
+        assert!(map.values().any(|m| m.hold));
+        // Upright I down the gap
+        assert!(map.keys().any(|f| f.m[36..] == [0b0_1_0_0, 0b0_1_0_0, 0b0_1_0_1, 0b1_1_1_1]));
+    }
 }
@@ -13,6 +13,8 @@
 //! ```
 
 pub mod solve;
+pub mod beam;
+pub mod mcts;
 pub mod gen_moves;
 pub mod field;
 pub mod state;
@@ -20,6 +22,19 @@ pub mod mov;
 pub mod evaluator;
 pub mod mac;
 pub mod config;
+pub mod bot;
+pub mod fumen;
+pub mod rng;
+pub mod sim;
+pub mod versus;
+pub mod replay;
+pub mod transposition;
+pub mod tree;
+pub mod stats;
+#[cfg(feature = "tbp")]
+pub mod tbp;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 pub use field::Field;
 pub use state::State;
@@ -27,6 +42,7 @@ pub use mov::Move;
 pub use gen_moves::gen_moves;
 pub use solve::solve;
 pub use evaluator::{evaluate, EvaluatorMode};
+pub use bot::Bot;
 
 // WASM CONSOLE
 // use wasm_bindgen::prelude::*;
@@ -61,6 +77,39 @@ pub enum Piece {
     None,
 }
 
+impl Piece {
+    /// All real piece types, in discriminant order.
+    pub const ALL: [Piece; 7] = [Piece::J, Piece::L, Piece::S, Piece::Z, Piece::T, Piece::I, Piece::O];
+
+    /// Parses a piece from its (case-insensitive) letter.
+    pub fn from_char (c: char) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            'J' => Some(Piece::J),
+            'L' => Some(Piece::L),
+            'S' => Some(Piece::S),
+            'Z' => Some(Piece::Z),
+            'T' => Some(Piece::T),
+            'I' => Some(Piece::I),
+            'O' => Some(Piece::O),
+            _ => None,
+        }
+    }
+
+    /// Letter of the piece. `Piece::None` is written as `-`.
+    pub fn to_char (self) -> char {
+        match self {
+            Piece::J => 'J',
+            Piece::L => 'L',
+            Piece::S => 'S',
+            Piece::Z => 'Z',
+            Piece::T => 'T',
+            Piece::I => 'I',
+            Piece::O => 'O',
+            Piece::None => '-',
+        }
+    }
+}
+
 /// Enumeration representing possible keystrokes.
 #[repr(u8)]
 #[derive(PartialEq, Clone, Debug)]
@@ -83,7 +132,7 @@ pub enum Key {
 ///
 /// Non-intuitive attributes:
 /// `sum_no_atk`: Downstack lines without an attack.
-#[derive(Copy, Clone, Debug, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Props {
     pub sum_atk: u8,
     pub sum_ds: u8,
@@ -92,7 +141,12 @@ pub struct Props {
     pub ds: u8,
     pub b2b: u8,
     pub combo: u8,
-    pub clears: u32,
+    pub clears: u64,
+    /// Garbage lines that entered the board on this placement.
+    pub garbage: u8,
+    /// Whether the placement lost the game: locked out inside the hidden buffer, or topped out by
+    /// garbage pushing filled cells off the top of the board.
+    pub top_out: bool,
 }
 
 impl Props {
@@ -106,6 +160,8 @@ impl Props {
             atk: 0,
             ds: 0,
             clears: 0,
+            garbage: 0,
+            top_out: false,
         }
     }
 }
@@ -0,0 +1,272 @@
+//! Command-line front-end of Tetron.
+//!
+//! ```text
+//! tetron solve <POSITION> [--depth N] [--mode MODE] [--time MS] [--hash MB] [--beam WIDTH] [--queue PIECES] [--hold PIECE] [--multi N]
+//! tetron play [--seed N] [--depth N] [--mode MODE] [--time MS] [--hash MB] [--beam WIDTH] [--pieces N] [--delay MS] [--record FILE]
+//! tetron replay <FILE> [--frame N] [--depth N] [--mode MODE] [--time MS] [--hash MB] [--beam WIDTH]
+//! tetron bench [--depth N] [--beam WIDTH]
+//! ```
+//! A position is a fumen string, or a file holding a board diagram (`-` reads stdin).
+
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tetron::config::{Config, Search};
+use tetron::sim::Game;
+use tetron::replay::Replay;
+use tetron::transposition::TranspositionTable;
+use tetron::{fumen, solve, EvaluatorMode, Piece, State};
+use tetron::solve::{solve_stats, solve_multi};
+
+const USAGE: &str = "usage:
+    tetron solve <POSITION> [--depth N] [--mode MODE] [--time MS] [--hash MB] [--beam WIDTH] [--queue PIECES] [--hold PIECE] [--multi N]
+    tetron play [--seed N] [--depth N] [--mode MODE] [--time MS] [--hash MB] [--beam WIDTH] [--pieces N] [--delay MS] [--record FILE]
+    tetron replay <FILE> [--frame N] [--depth N] [--mode MODE] [--time MS] [--hash MB] [--beam WIDTH]
+    tetron bench [--depth N] [--beam WIDTH]
+
+POSITION is a fumen string, or a file holding a board diagram ('-' for stdin).
+MODE is one of norm, attack, ds.
+--time bounds each solve, deepening iteratively up to --depth.
+--hash sizes the transposition table, kept across the moves of a game.
+--beam switches to a beam search of the given width.
+--multi lists the N best moves with their margin to the best.";
+
+/// Positions solved by `bench`.
+const BENCH_SUITE: [(&str, &str); 4] = [
+    ("empty", "
+        queue: TIOSZJL
+        ..........
+    "),
+    ("tsd setup", "
+        queue: TLJSZ  hold: I
+        ##........
+        ##.......#
+        ###....###
+        ####..####
+        #####.####
+    "),
+    ("downstack", "
+        queue: OSZIT
+        ...#......
+        #..##...##
+        ##.###.###
+        ##.#######
+        ####.#####
+        ###.######
+    "),
+    ("tall well", "
+        queue: IJLTO  hold: S
+        ##.#######
+        ######.###
+        ####.#####
+        #######.##
+        #####.####
+        ###.######
+        ########.#
+        #.########
+    "),
+];
+
+/// Parsed `--flag value` options.
+struct Options {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+}
+
+impl Options {
+    fn parse (args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut flags = vec![];
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            if let Some(flag) = arg.strip_prefix("--") {
+                let value = args.next().ok_or(format!("missing value for --{}", flag))?;
+                flags.push((flag.to_string(), value));
+            } else {
+                positional.push(arg);
+            }
+        }
+        Ok(Self { positional, flags })
+    }
+
+    fn get<T: std::str::FromStr> (&self, flag: &str, default: T) -> Result<T, String> {
+        match self.flags.iter().rev().find(|(f, _)| f == flag) {
+            Some((_, v)) => v.parse().map_err(|_| format!("invalid value '{}' for --{}", v, flag)),
+            None => Ok(default),
+        }
+    }
+
+    fn str (&self, flag: &str) -> Option<&str> {
+        self.flags.iter().rev().find(|(f, _)| f == flag).map(|(_, v)| v.as_str())
+    }
+
+    fn config (&self, depth: u8) -> Result<Config, String> {
+        let mode: EvaluatorMode = self.str("mode").unwrap_or("norm").parse()?;
+        let time: u64 = self.get("time", 0)?;
+        let hash: usize = self.get("hash", 0)?;
+        let beam: usize = self.get("beam", 0)?;
+        Ok(Config {
+            search: if beam > 0 { Search::Beam { width: beam } } else { Search::Dfs },
+            time: (time > 0).then(|| Duration::from_millis(time)),
+            tt: (hash > 0).then(|| Arc::new(TranspositionTable::with_megabytes(hash))),
+            ..Config::new(self.get("depth", depth)?, mode)
+        })
+    }
+}
+
+fn parse_pieces (s: &str) -> Result<Vec<Piece>, String> {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| Piece::from_char(c).ok_or(format!("invalid piece '{}'", c)))
+        .collect()
+}
+
+fn load_position (arg: &str) -> Result<State, String> {
+    if arg.contains("115@") {
+        return State::from_fumen(arg).map_err(|e| e.to_string());
+    }
+    let mut text = String::new();
+    if arg == "-" {
+        std::io::stdin().read_to_string(&mut text).map_err(|e| e.to_string())?;
+    } else {
+        text = std::fs::read_to_string(arg).map_err(|e| format!("{}: {}", arg, e))?;
+    }
+    text.parse().map_err(|e: tetron::field::ParseError| e.to_string())
+}
+
+fn cmd_solve (opts: &Options) -> Result<(), String> {
+    let position = opts.positional.first().ok_or("missing position")?;
+    let mut state = load_position(position)?;
+    if let Some(queue) = opts.str("queue") {
+        state.pieces = parse_pieces(queue)?.into();
+        state.infer_bag();
+    }
+    if let Some(hold) = opts.str("hold") {
+        state.hold = parse_pieces(hold)?.first().copied().unwrap_or(Piece::None);
+    }
+    let config = opts.config(2)?.fit(&state);
+
+    let (res, stats) = solve_stats(&state, &config);
+    let Some((line, depth)) = res else {
+        println!("No results found.");
+        return Ok(());
+    };
+    let out = &line[0];
+
+    println!("{}", state);
+    println!("move:  {:?}", out.1);
+    println!("keys:  {:?}", out.1.parse_list());
+    println!("score: \x1b[1m{}\x1b[0m", out.2);
+    println!("props: {:?}", out.0.props);
+    println!("depth: {}, dt: {}ms", depth, stats.time.as_millis());
+    println!("nodes: {:?} ({} total), moves: {}, pruned: {}, conflicts: {}, tt hits: {}",
+        stats.nodes, stats.total_nodes(), stats.moves, stats.pruned, stats.conflicts, stats.tt_hits);
+    println!("\n{}", out.0);
+    println!("plan:");
+    let mut prev = &state;
+    for (i, (child, mov, score)) in line.iter().enumerate() {
+        let spin = if mov.tspin {" tspin"} else {""};
+        println!("  {:>2}. {:?} ({}, {}) r{}{}  score: {}", i + 1, prev.placed_piece(mov), mov.x, mov.y, mov.r, spin, score);
+        prev = child;
+    }
+    if let Ok(data) = fumen::encode_line(&state, &line) {
+        println!("fumen: {}", data);
+    }
+
+    let multi: usize = opts.get("multi", 0)?;
+    if multi > 1 {
+        println!("candidates:");
+        for (i, c) in solve_multi(&state, &config, multi).iter().enumerate() {
+            let mov = &c.mov;
+            println!("  {:>2}. {:?} ({}, {}) r{}  score: {}, margin: {}, keys: {:?}", i + 1, state.placed_piece(mov), mov.x, mov.y, mov.r, c.score, c.score - out.2, c.keys);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_play (opts: &Options) -> Result<(), String> {
+    let config = opts.config(2)?;
+    let mut game = Game::new(opts.get("seed", 0)?);
+    let max_pieces: u32 = opts.get("pieces", u32::MAX)?;
+    let delay = Duration::from_millis(opts.get("delay", 0)?);
+
+    while !game.over && game.stats.pieces < max_pieces {
+        game.step(&config);
+        // Clear the terminal & redraw
+        print!("\x1b[2J\x1b[H{}", game.state);
+        println!("pieces: {}, lines: {}, attack: {}, pps: {:.2}, app: {:.3}",
+            game.stats.pieces, game.stats.lines, game.stats.attack, game.stats.pps(), game.stats.app());
+        std::thread::sleep(delay);
+    }
+    if game.over {
+        println!("Topped out.");
+    }
+    if let Some(path) = opts.str("record") {
+        std::fs::write(path, game.replay.to_string()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn cmd_replay (opts: &Options) -> Result<(), String> {
+    let path = opts.positional.first().ok_or("missing replay file")?;
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let replay: Replay = text.parse().map_err(|e: tetron::replay::ReplayError| e.to_string())?;
+
+    let i: usize = opts.get("frame", replay.frames.len().saturating_sub(1))?;
+    let state = replay.frame(i).map_err(|e| e.to_string())?;
+    println!("frame {} / {}", i, replay.frames.len());
+    println!("{}", state);
+    if let Some(frame) = replay.frames.get(i) {
+        println!("recorded: {:?}", frame.mov);
+    }
+
+    let config = opts.config(2)?.fit(&state);
+    match solve(&state, &config) {
+        Some(out) => {
+            println!("solved:   {:?}", out.1);
+            println!("score: \x1b[1m{}\x1b[0m, depth: {}", out.2, config.depth);
+        },
+        None => println!("No results found."),
+    }
+    Ok(())
+}
+
+fn cmd_bench (opts: &Options) -> Result<(), String> {
+    let config = opts.config(2)?;
+    tetron::bench_reset();
+    if !cfg!(feature = "bench") {
+        println!("note: build with `--features bench` for per-function timings.");
+    }
+
+    let mut total = Duration::ZERO;
+    for (name, diagram) in BENCH_SUITE {
+        let state: State = diagram.parse().map_err(|e: tetron::field::ParseError| e.to_string())?;
+        tetron::bench_increment_solve();
+
+        let start = Instant::now();
+        let out = solve(&state, &config.fit(&state));
+        let dt = start.elapsed();
+        total += dt;
+        println!("{:<10} dt: {:>6}ms, score: {}", name, dt.as_millis(), out.map_or(f32::NEG_INFINITY, |o| o.2));
+    }
+    println!("total: {}ms", total.as_millis());
+    tetron::print_bench_result();
+    Ok(())
+}
+
+fn main () {
+    let mut args = std::env::args().skip(1);
+    let cmd = args.next().unwrap_or_default();
+    let res = Options::parse(args).and_then(|opts| match cmd.as_str() {
+        "solve" => cmd_solve(&opts),
+        "play" => cmd_play(&opts),
+        "replay" => cmd_replay(&opts),
+        "bench" => cmd_bench(&opts),
+        _ => Err(USAGE.to_string()),
+    });
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+}
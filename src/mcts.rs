@@ -0,0 +1,346 @@
+//! Module implementing the Monte Carlo tree search engine.
+//!
+//! Plans past the visible queue: nodes short of pieces sample them from the 7-bag, each node
+//! keeping its own sample. Expanding a node evaluates all its placements with `evaluate()`, and
+//! the best child's value is backed up. Selection follows UCT over values normalized to [0, 1].
+//!
+//! The tree persists between calls: `search()` grows it incrementally, `play_move()` re-roots it
+//! and `add_next_piece()` prunes the lines whose sampled piece turned out wrong.
+
+use std::time::{Duration, Instant};
+
+use super::{State, Move, Piece, gen_moves, evaluate};
+use super::evaluator::EvaluatorMode;
+use super::rng::Rng;
+use super::state::bag_pieces;
+
+struct Node {
+    state: State,
+    /// Move from the parent, `None` at the root.
+    mov: Option<Move>,
+    children: Vec<usize>,
+    expanded: bool,
+    visits: u32,
+    /// Sum of backed up values.
+    value: f32,
+    /// Index, relative to the root's queue, of `state.pieces[0]`.
+    offset: usize,
+}
+
+impl Node {
+    fn new (state: State, mov: Option<Move>, value: f32, offset: usize) -> Self {
+        Self {
+            state,
+            mov,
+            children: vec![],
+            expanded: false,
+            visits: 1,
+            value,
+            offset,
+        }
+    }
+
+    fn mean (&self) -> f32 {
+        self.value / self.visits as f32
+    }
+}
+
+/// Persistent MCTS tree.
+pub struct Mcts {
+    nodes: Vec<Node>,
+    root: usize,
+    pub eval_mode: EvaluatorMode,
+    /// UCT exploration constant.
+    pub exploration: f32,
+    rng: Rng,
+    /// Range of values seen, used to normalize them.
+    bounds: (f32, f32),
+    /// Number of real (not sampled) pieces at the front of the root's queue.
+    known: usize,
+    /// Pieces left in the bag after the last real piece.
+    bag: u8,
+}
+
+impl Mcts {
+    pub fn new (state: State, eval_mode: EvaluatorMode, seed: u64) -> Self {
+        let value = evaluate(&state, eval_mode);
+        let (known, bag) = (state.pieces.len(), state.bag);
+        Self {
+            nodes: vec![Node::new(state, None, value, 0)],
+            root: 0,
+            eval_mode,
+            exploration: 1.0,
+            rng: Rng::new(seed),
+            bounds: (value, value),
+            known,
+            bag,
+        }
+    }
+
+    /// State at the root of the tree.
+    pub fn state (&self) -> &State {
+        &self.nodes[self.root].state
+    }
+
+    /// Number of nodes in the tree.
+    pub fn size (&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn normalize (&self, v: f32) -> f32 {
+        let (min, max) = self.bounds;
+        if max > min { (v - min) / (max - min) } else { 0.5 }
+    }
+
+    /// Runs `iterations` of selection, expansion and backup.
+    pub fn search (&mut self, iterations: u32) {
+        for _ in 0..iterations {
+            self.iterate();
+        }
+    }
+
+    /// Searches until `time` has elapsed.
+    pub fn search_for (&mut self, time: Duration) {
+        let deadline = Instant::now() + time;
+        while Instant::now() < deadline {
+            self.iterate();
+        }
+    }
+
+    fn iterate (&mut self) {
+        // Selection
+        let mut path: Vec<usize> = vec![self.root];
+        let mut i = self.root;
+        while self.nodes[i].expanded && !self.nodes[i].children.is_empty() {
+            i = self.select(i);
+            path.push(i);
+        }
+
+        // Expansion, topped out leaves back up the lowest value seen
+        let value = if self.nodes[i].expanded {
+            self.bounds.0
+        } else {
+            self.expand(i).unwrap_or(self.bounds.0)
+        };
+
+        // Backup
+        for &j in path.iter() {
+            self.nodes[j].visits += 1;
+            self.nodes[j].value += value;
+        }
+    }
+
+    /// UCT child of node `i`.
+    fn select (&self, i: usize) -> usize {
+        let ln_n = (self.nodes[i].visits as f32).ln();
+        let uct = |c: usize| {
+            let node = &self.nodes[c];
+            self.normalize(node.mean()) + self.exploration * (ln_n / node.visits as f32).sqrt()
+        };
+        *self.nodes[i].children.iter()
+            .max_by(|&&a, &&b| uct(a).total_cmp(&uct(b)))
+            .unwrap()
+    }
+
+    /// Samples the next unknown piece from the bag of node `i`.
+    fn sample (&mut self, i: usize) {
+        let pieces: Vec<Piece> = bag_pieces(self.nodes[i].state.bag).collect();
+        let p = pieces[self.rng.below(pieces.len())];
+        self.nodes[i].state.push_piece(p);
+    }
+
+    /// Expands node `i`, returning the best value among its children.
+    fn expand (&mut self, i: usize) -> Option<f32> {
+        // Current piece & hold alternative
+        while self.nodes[i].state.pieces.len() < 2 {
+            self.sample(i);
+        }
+        self.nodes[i].expanded = true;
+
+        let node = &self.nodes[i];
+        let mut children: Vec<Node> = vec![];
+        for (field, mov) in gen_moves(&node.state) {
+            let state = node.state.clone_as_child(field, &mov);
+            let value = evaluate(&state, self.eval_mode);
+            let offset = node.offset + node.state.pieces.len() - state.pieces.len();
+            children.push(Node::new(state, Some(mov), value, offset));
+        }
+
+        let best = children.iter().map(|c| c.value).max_by(f32::total_cmp)?;
+        for c in children.iter() {
+            self.bounds = (self.bounds.0.min(c.value), self.bounds.1.max(c.value));
+        }
+        let start = self.nodes.len();
+        self.nodes.extend(children);
+        self.nodes[i].children = (start..self.nodes.len()).collect();
+        Some(best)
+    }
+
+    /// Most visited move at the root, with its mean value.
+    pub fn best_move (&self) -> Option<(Move, f32)> {
+        let root = &self.nodes[self.root];
+        let best = root.children.iter().max_by_key(|&&c| self.nodes[c].visits)?;
+        let node = &self.nodes[*best];
+        Some((node.mov.clone()?, node.mean()))
+    }
+
+    /// Re-roots the tree on the child reached by `mov`, keeping its subtree.
+    ///
+    /// Starts a new tree if that child was never generated. Returns false if `mov` cannot be played
+    /// with the real pieces.
+    pub fn play_move (&mut self, mov: &Move) -> bool {
+        let mut real = self.state().clone();
+        real.pieces.truncate(self.known);
+        let Some(next) = real.play(mov) else {
+            return false;
+        };
+        let child = self.nodes[self.root].children.iter()
+            .copied()
+            .find(|&c| self.nodes[c].state.same_position(&next));
+        match child {
+            Some(c) => {
+                let shift = self.nodes[c].offset;
+                self.known = self.known.saturating_sub(shift);
+                self.root = c;
+                self.compact(shift);
+            },
+            None => {
+                let value = evaluate(&next, self.eval_mode);
+                let mut root = Node::new(next, None, value, 0);
+                root.state.bag = self.bag;
+                self.known = root.state.pieces.len();
+                self.nodes = vec![root];
+                self.root = 0;
+            },
+        }
+        true
+    }
+
+    /// Reveals the next piece of the queue.
+    ///
+    /// Nodes that sampled another piece in its place are reset with the real one, dropping their subtree.
+    pub fn add_next_piece (&mut self, piece: Piece) {
+        let index = self.known;
+        self.known += 1;
+        self.bag = crate::state::bag_draw(self.bag, piece);
+
+        let mut stack = vec![self.root];
+        while let Some(i) = stack.pop() {
+            let node = &mut self.nodes[i];
+            match index.checked_sub(node.offset) {
+                Some(k) if k < node.state.pieces.len() && node.state.pieces[k] != piece => {
+                    node.state.pieces.truncate(k);
+                    node.state.pieces.push_back(piece);
+                    node.state.bag = self.bag;
+                    node.children.clear();
+                    node.expanded = false;
+                    node.visits = 1;
+                    node.value = evaluate(&node.state, self.eval_mode);
+                    continue;
+                },
+                Some(k) if k == node.state.pieces.len() => {
+                    node.state.pieces.push_back(piece);
+                    node.state.bag = self.bag;
+                },
+                _ => {},
+            }
+            stack.extend(self.nodes[i].children.iter().copied());
+        }
+        self.compact(0);
+    }
+
+    /// Drops the nodes unreachable from the root, shifting queue offsets by `shift`.
+    fn compact (&mut self, shift: usize) {
+        let mut order: Vec<usize> = vec![self.root];
+        let mut k = 0;
+        while k < order.len() {
+            order.extend(self.nodes[order[k]].children.iter().copied());
+            k += 1;
+        }
+        let mut map: Vec<usize> = vec![usize::MAX; self.nodes.len()];
+        for (n, &i) in order.iter().enumerate() {
+            map[i] = n;
+        }
+
+        let mut old: Vec<Option<Node>> = std::mem::take(&mut self.nodes).into_iter().map(Some).collect();
+        self.nodes = order.iter()
+            .map(|&i| {
+                let mut node = old[i].take().unwrap();
+                node.children.iter_mut().for_each(|c| *c = map[*c]);
+                node.offset -= shift;
+                node
+            })
+            .collect();
+        self.root = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mcts_search_test () {
+        let state: State = "
+            queue: TIO
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+        let mut mcts = Mcts::new(state, EvaluatorMode::Norm, 1);
+        mcts.search(300);
+        let size = mcts.size();
+        let (mov, score) = mcts.best_move().unwrap();
+        println!("size: {}, score: {}, move: {:?}", size, score, mov);
+
+        // Plans past the 3 visible pieces
+        let deepest = mcts.nodes.iter().map(|n| n.offset).max().unwrap();
+        println!("deepest: {}", deepest);
+        assert!(deepest > 3);
+
+        // Incremental growth
+        mcts.search(100);
+        assert!(mcts.size() > size);
+
+        // Re-rooting keeps the subtree & real queue
+        assert!(mcts.play_move(&mov));
+        assert_eq!(mcts.known, if mov.hold {1} else {2});
+        assert!(mcts.size() > 1);
+        assert!(mcts.nodes.iter().all(|n| n.children.iter().all(|&c| c < mcts.size())));
+
+        // Revealed pieces are consistent across the tree
+        mcts.add_next_piece(Piece::S);
+        let known = mcts.known;
+        for node in mcts.nodes.iter() {
+            if node.offset < known {
+                assert_eq!(node.state.pieces.get(known - 1 - node.offset), Some(&Piece::S));
+            }
+        }
+        mcts.search(100);
+        assert!(mcts.best_move().is_some());
+        assert!(mcts.play_move(&mcts.best_move().unwrap().0));
+    }
+
+    #[test]
+    fn mcts_variant_test () {
+        let state: State = "
+            queue: IOT
+            #########.
+            #########.
+        ".parse().unwrap();
+        let mut mcts = Mcts::new(state.clone(), EvaluatorMode::Norm, 1);
+        mcts.search(50);
+
+        // The other t-spin variant of a generated clear reaches another position, so it starts anew
+        let mov = mcts.nodes[mcts.root].children.iter()
+            .map(|&c| &mcts.nodes[c])
+            .find(|c| c.state.props.ds > 0)
+            .and_then(|c| c.mov.clone())
+            .map(|m| Move { tspin: !m.tspin, ..m })
+            .unwrap();
+        let next = state.play(&mov).unwrap();
+        assert!(mcts.play_move(&mov));
+        assert_eq!(mcts.state().props, next.props);
+        assert_eq!(mcts.size(), 1);
+    }
+}
@@ -1,5 +1,5 @@
 use super::{Key, Piece, Field};
-use crate::field::ConflictCache;
+use crate::field::{ConflictCache, FIELD_H, BUFFER_H};
 
 /// Minimalist structure containing properties of a piece placement.
 ///
@@ -17,11 +17,11 @@ pub struct Move {
 }
 
 impl Move {
-    /// Spanws new `Move` instance.
+    /// Spanws new `Move` instance, at the spawn position of the standard board (see `Field::spawn()`).
     pub fn new () -> Self {
         Self {
             x: 4,
-            y: 1,
+            y: BUFFER_H as i8 - 1,
             r: 0,
             s: -1, 
             tspin: false,
@@ -95,6 +95,16 @@ impl Move {
         self.list += 1; // Increment counter
     }
 
+    /// Drops the piece through the empty rows above the stack at once.
+    ///
+    /// Pieces span at most 2 rows below their center, so those rows cannot conflict.
+    fn skip_empty_rows (&mut self, field: &Field) {
+        let floor = field.stack_top() as i8 - 3;
+        if self.y < floor {
+            self.y = floor;
+        }
+    }
+
     ///  Function managing spins & kicks.
     ///
     ///  Behavior in accordance with the SRS kicktable.
@@ -125,7 +135,7 @@ impl Move {
                     let cnt: u8 = 
                         [(self.x-1, self.y-1), (self.x-1, self.y+1), (self.x+1, self.y-1), (self.x+1 , self.y+1)]
                         .map(|(x, y)| 
-                            if x < 0 || y < 0 || x >= 10 || y >= 20 || field.m[y as usize] & (1 << x) > 0 {1 as u8} else {0 as u8}
+                            if x < 0 || y < field.ceiling() as i8 || x >= field.width as i8 || y >= FIELD_H as i8 || field.m[y as usize] & (1 << x) > 0 {1 as u8} else {0 as u8}
                         ).iter().sum::<u8>();
                     self.tspin = cnt >= 3;
                 }
@@ -188,6 +198,7 @@ impl Move {
                     return false;
                 }
 
+                self.skip_empty_rows(field);
                 while !field.check_conflict(&mut cache, &*self, p) {
                     self.y += 1;
                 }
@@ -195,6 +206,7 @@ impl Move {
                 self.s = -2; // Read comment on declaration. Spin tracking.
             },
             Key::HardDrop => {
+                self.skip_empty_rows(field);
                 while !field.check_conflict(&mut cache, &*self, p) {
                     self.y += 1;
                 }
@@ -250,12 +262,12 @@ mod tests {
         let field: Field = Field::new();
         let mut mov: Move = Move::new();
         let p: Piece = Piece::L;
-        let cache: ConflictCache = [[0; 20]; 4]; 
+        let cache: ConflictCache = [[0; FIELD_H]; 4]; 
 
         mov.apply_key(&Key::HardDrop, &mut (cache, cache), &field, &p, &p);
 
         assert_eq!(mov.x, 4);
-        assert_eq!(mov.y, 19);
+        assert_eq!(mov.y, 39);
         assert_eq!(mov.r, 0);
     }
 }
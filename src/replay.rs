@@ -0,0 +1,394 @@
+//! Module implementing the replay format.
+//!
+//! A replay stores how pieces were dealt (a 7-bag seed or an explicit piece sequence), the
+//! starting position and every placement with its resulting `Props`, along with the garbage
+//! queued by the opponent. Playing it back rebuilds each intermediate `State` with
+//! `clone_as_child`, so any frame can be re-solved.
+//!
+//! Text format, one entry per line:
+//! ```text
+//! tetron-replay
+//! seed 42                 (or: queue TIOSZJL...)
+//! preview 5
+//! size 10 20              (board width and visible height)
+//! hold T                  (optional starting position)
+//! props 1 0               (b2b combo)
+//! row 39 1ff              (row index, hex bits)
+//! garbage 2 3 0           (lines hole delay), queued on the starting position
+//! 4 38 0 - 0 0 0 0 0 2    (x y r flags atk ds b2b combo clears garbage), flags: h = hold, t = t-spin
+//! garbage 1 7 0           queued after the placement above
+//! ```
+//! The trailing `garbage` token of a frame (lines that entered the board) may be omitted, meaning 0.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{State, Move, Piece, Props, solve};
+use super::state::{FULL_BAG, Garbage};
+use super::field::{Field, FIELD_H, MAX_W, MIN_W, VISIBLE_H};
+use super::config::Config;
+use super::sim::Bag;
+
+/// A recorded placement.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub mov: Move,
+    /// Properties of the state after the placement.
+    pub props: Props,
+    /// Garbage the opponent queued after the placement, oldest first.
+    pub garbage: Vec<Garbage>,
+}
+
+/// Error raised when reading or playing back a replay.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ReplayError {
+    /// Missing `tetron-replay` header.
+    Header,
+    /// Malformed line (1-indexed).
+    Line(usize),
+    /// Frame whose move could not be placed, or whose props differ from the recording.
+    Desync(usize),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Header => write!(f, "not a tetron replay"),
+            ReplayError::Line(n) => write!(f, "malformed line {}", n),
+            ReplayError::Desync(i) => write!(f, "replay desyncs at frame {}", i),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
+
+/// Recorded game.
+#[derive(Clone)]
+pub struct Replay {
+    /// Seed of the 7-bag dealing the pieces; `None` deals from `queue`.
+    pub seed: Option<u64>,
+    /// Dealt pieces, when not seeded.
+    pub queue: Vec<Piece>,
+    /// Number of visible pieces after the current one.
+    pub preview: usize,
+    /// Starting position. Its queue is ignored, pieces are dealt from the seed or `queue`.
+    pub start: State,
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    /// Replay of a game dealt by a seeded 7-bag, starting from an empty board.
+    pub fn seeded (seed: u64, preview: usize) -> Self {
+        Self {
+            seed: Some(seed),
+            queue: vec![],
+            preview,
+            start: State::new(),
+            frames: vec![],
+        }
+    }
+
+    /// Replay starting from a given state, dealing its queue followed by pieces added with `add_piece`.
+    pub fn from_state (state: &State, preview: usize) -> Self {
+        Self {
+            seed: None,
+            queue: state.pieces.iter().copied().collect(),
+            preview,
+            start: state.clone(),
+            frames: vec![],
+        }
+    }
+
+    /// Appends a piece to the dealt sequence of an unseeded replay.
+    pub fn add_piece (&mut self, piece: Piece) {
+        self.queue.push(piece);
+    }
+
+    /// Records a placement and the properties it resulted in.
+    pub fn record (&mut self, mov: &Move, props: &Props) {
+        self.frames.push(Frame { mov: mov.clone(), props: *props, garbage: vec![] });
+    }
+
+    /// Records garbage queued by the opponent, after the last placement or on the starting position.
+    pub fn add_garbage (&mut self, garbage: Garbage) {
+        match self.frames.last_mut() {
+            Some(frame) => frame.garbage.push(garbage),
+            None => self.start.garbage.push_back(garbage),
+        }
+    }
+
+    /// Solves `state` and records the chosen move.
+    pub fn solve (&mut self, state: &State, config: &Config) -> Option<(State, Move, f32)> {
+        let res = solve(state, config)?;
+        let props = state.play(&res.1)?.props;
+        self.record(&res.1, &props);
+        Some(res)
+    }
+
+    /// Rebuilds the state before every frame, followed by the final state.
+    pub fn states (&self) -> Result<Vec<State>, ReplayError> {
+        let mut dealer: Box<dyn Iterator<Item = Piece>> = match self.seed {
+            Some(seed) => Box::new(Bag::new(seed)),
+            None => Box::new(self.queue.clone().into_iter()),
+        };
+        let deal = |state: &mut State, dealer: &mut Box<dyn Iterator<Item = Piece>>| {
+            while state.pieces.len() < self.preview + 1 {
+                let Some(p) = dealer.next() else { break };
+                state.push_piece(p);
+            }
+        };
+
+        let mut state = self.start.clone();
+        state.pieces.clear();
+        state.bag = FULL_BAG;
+        deal(&mut state, &mut dealer);
+
+        let mut states = Vec::with_capacity(self.frames.len() + 1);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let Some(mut child) = state.play(&frame.mov) else {
+                return Err(ReplayError::Desync(i));
+            };
+            let (a, b) = (&child.props, &frame.props);
+            if (a.atk, a.ds, a.b2b, a.combo, a.clears, a.garbage) != (b.atk, b.ds, b.b2b, b.combo, b.clears, b.garbage) {
+                return Err(ReplayError::Desync(i));
+            }
+            deal(&mut child, &mut dealer);
+            child.garbage.extend(frame.garbage.iter().copied());
+            states.push(state);
+            state = child;
+        }
+        states.push(state);
+        Ok(states)
+    }
+
+    /// State before frame `i`, or the final state for `i == frames.len()`.
+    pub fn frame (&self, i: usize) -> Result<State, ReplayError> {
+        let mut states = self.states()?;
+        if i >= states.len() {
+            return Err(ReplayError::Desync(i));
+        }
+        Ok(states.swap_remove(i))
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "tetron-replay")?;
+        match self.seed {
+            Some(seed) => writeln!(f, "seed {}", seed)?,
+            None => writeln!(f, "queue {}", self.queue.iter().map(|p| p.to_char()).collect::<String>())?,
+        }
+        writeln!(f, "preview {}", self.preview)?;
+        writeln!(f, "size {} {}", self.start.field.width, self.start.field.height)?;
+        if self.start.hold != Piece::None {
+            writeln!(f, "hold {}", self.start.hold.to_char())?;
+        }
+        if self.start.props.b2b > 0 || self.start.props.combo > 0 {
+            writeln!(f, "props {} {}", self.start.props.b2b, self.start.props.combo)?;
+        }
+        for (y, row) in self.start.field.m.iter().enumerate() {
+            if *row != 0 {
+                writeln!(f, "row {} {:x}", y, row)?;
+            }
+        }
+        let garbage = |f: &mut fmt::Formatter, g: &Garbage| writeln!(f, "garbage {} {} {}", g.lines, g.hole, g.delay);
+        for g in self.start.garbage.iter() {
+            garbage(f, g)?;
+        }
+        for frame in self.frames.iter() {
+            let (m, p) = (&frame.mov, &frame.props);
+            let flags = match (m.hold, m.tspin) {
+                (false, false) => "-",
+                (true, false) => "h",
+                (false, true) => "t",
+                (true, true) => "ht",
+            };
+            writeln!(f, "{} {} {} {} {} {} {} {} {:x} {}", m.x, m.y, m.r, flags, p.atk, p.ds, p.b2b, p.combo, p.clears, p.garbage)?;
+            for g in frame.garbage.iter() {
+                garbage(f, g)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = ReplayError;
+
+    fn from_str (s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+        if lines.next().map(|(_, l)| l.trim()) != Some("tetron-replay") {
+            return Err(ReplayError::Header);
+        }
+
+        let mut replay = Replay::seeded(0, 5);
+        replay.seed = None;
+        for (n, line) in lines {
+            let err = || ReplayError::Line(n + 1);
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let byte = |i: usize| token::<u8>(&tokens, i).ok_or_else(err);
+            match tokens[0] {
+                "seed" => replay.seed = Some(token(&tokens, 1).ok_or_else(err)?),
+                "queue" => {
+                    let pieces = tokens.get(1).unwrap_or(&"");
+                    replay.queue = pieces.chars().map(Piece::from_char).collect::<Option<_>>().ok_or_else(err)?;
+                },
+                "preview" => replay.preview = token(&tokens, 1).ok_or_else(err)?,
+                "size" => {
+                    let (width, height) = (byte(1)? as usize, byte(2)? as usize);
+                    if !(MIN_W..=MAX_W).contains(&width) || !(1..=VISIBLE_H).contains(&height) {
+                        return Err(err());
+                    }
+                    replay.start.field = Field { m: replay.start.field.m, ..Field::with_size(width, height) };
+                },
+                "hold" => {
+                    let c = tokens.get(1).and_then(|t| t.chars().next()).ok_or_else(err)?;
+                    replay.start.hold = if c == '-' { Piece::None } else { Piece::from_char(c).ok_or_else(err)? };
+                },
+                "props" => {
+                    replay.start.props.b2b = byte(1)?;
+                    replay.start.props.combo = byte(2)?;
+                },
+                "garbage" => {
+                    let garbage = Garbage { lines: byte(1)?, hole: byte(2)?, delay: byte(3)? };
+                    if garbage.hole >= replay.start.field.width {
+                        return Err(err());
+                    }
+                    replay.add_garbage(garbage);
+                },
+                "row" => {
+                    let y: usize = token(&tokens, 1).ok_or_else(err)?;
+                    let row = tokens.get(2).and_then(|t| u16::from_str_radix(t, 16).ok()).ok_or_else(err)?;
+                    *replay.start.field.m.get_mut(y).ok_or_else(err)? = row;
+                },
+                _ => {
+                    if tokens.len() != 9 && tokens.len() != 10 {
+                        return Err(err());
+                    }
+                    let flags = tokens[3];
+                    let coord = |i: usize, max: usize| byte(i).and_then(|v| if (v as usize) < max {Ok(v)} else {Err(err())});
+                    let mov = Move {
+                        x: coord(0, MAX_W)? as i8,
+                        y: coord(1, FIELD_H)? as i8,
+                        r: coord(2, 4)?,
+                        hold: flags.contains('h'),
+                        tspin: flags.contains('t'),
+                        lock: true,
+                        ..Move::new()
+                    };
+                    let mut props = Props::new();
+                    props.atk = byte(4)?;
+                    props.ds = byte(5)?;
+                    props.b2b = byte(6)?;
+                    props.combo = byte(7)?;
+                    props.clears = u64::from_str_radix(tokens[8], 16).map_err(|_| err())?;
+                    if tokens.len() == 10 {
+                        props.garbage = byte(9)?;
+                    }
+                    replay.record(&mov, &props);
+                },
+            }
+        }
+        Ok(replay)
+    }
+}
+
+/// Token `i` of a line, `None` if missing, malformed or out of the range of `T`.
+fn token<T: FromStr> (tokens: &[&str], i: usize) -> Option<T> {
+    tokens.get(i)?.parse().ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvaluatorMode;
+    use crate::sim::Game;
+
+    #[test]
+    fn replay_round_trip_test () {
+        let config = Config::new(1, EvaluatorMode::Norm);
+        let mut game = Game::new(11);
+        game.run(&config, 20);
+
+        let text = game.replay.to_string();
+        println!("{}", text);
+        let replay: Replay = text.parse().unwrap();
+        assert_eq!(replay.frames.len(), 20);
+
+        // Playback reaches the same final position
+        let states = replay.states().unwrap();
+        assert_eq!(states.len(), 21);
+        assert_eq!(states[20].field, game.state.field);
+        assert_eq!(states[20].pieces, game.state.pieces);
+
+        // Any frame can be re-solved with another config
+        let state = replay.frame(10).unwrap();
+        assert!(solve(&state, &Config::new(0, EvaluatorMode::DS)).is_some());
+    }
+
+    #[test]
+    fn replay_recorder_test () {
+        let mut state: State = "
+            queue: TIO  hold: S
+            #####..###
+            ######.###
+        ".parse().unwrap();
+        let config = Config::new(0, EvaluatorMode::Norm);
+        let mut replay = Replay::from_state(&state, 2);
+        for _ in 0..3 {
+            let (_, mov, _) = replay.solve(&state, &config).unwrap();
+            state = state.play(&mov).unwrap();
+        }
+        assert_eq!(replay.frames.len(), 3);
+
+        let parsed: Replay = replay.to_string().parse().unwrap();
+        let states = parsed.states().unwrap();
+        assert_eq!(states[0].hold, Piece::S);
+        assert_eq!(states[3].field, state.field);
+
+        // Tampered props are reported as a desync
+        let mut bad = parsed.clone();
+        bad.frames[1].props.atk += 1;
+        assert_eq!(bad.states().err(), Some(ReplayError::Desync(1)));
+
+        // Moves off the board or overlapping the stack desync, malformed ones fail to parse
+        let mut bad = parsed.clone();
+        bad.frames[0].mov.x = 9;
+        bad.frames[0].mov.r = 0;
+        assert_eq!(bad.states().err(), Some(ReplayError::Desync(0)));
+        bad.frames[0].mov = Move { x: 4, y: 39, lock: true, ..Move::new() };
+        assert_eq!(bad.states().err(), Some(ReplayError::Desync(0)));
+        let text = parsed.to_string();
+        let line = text.lines().count();
+        for frame in ["4 20 4 - 0 0 0 0 0", "-1 20 0 - 0 0 0 0 0", "4 99 0 - 0 0 0 0 0", "4 20 0 - 0 0 300 0 0", "garbage 1 10 0", "garbage 300 0 0"] {
+            assert_eq!(format!("{}{}", text, frame).parse::<Replay>().err(), Some(ReplayError::Line(line + 1)));
+        }
+        assert_eq!("tetron-replay\nprops 300 0".parse::<Replay>().err(), Some(ReplayError::Line(2)));
+        assert_eq!("garbage".parse::<Replay>().err(), Some(ReplayError::Header));
+    }
+
+    #[test]
+    fn replay_size_test () {
+        let mut state = State::new();
+        state.field = Field::with_size(4, 12);
+        state.pieces.extend([Piece::I, Piece::O, Piece::I, Piece::O]);
+        let config = Config::new(0, EvaluatorMode::Norm);
+        let mut replay = Replay::from_state(&state, 1);
+        for _ in 0..3 {
+            let (_, mov, _) = replay.solve(&state, &config).unwrap();
+            state = state.play(&mov).unwrap();
+        }
+
+        // The board size survives the text format and playback
+        let text = replay.to_string();
+        assert!(text.contains("size 4 12"));
+        let parsed: Replay = text.parse().unwrap();
+        let states = parsed.states().unwrap();
+        assert_eq!((states[0].field.width, states[0].field.height), (4, 12));
+        assert_eq!(states[3].field, state.field);
+
+        let line = text.lines().position(|l| l.starts_with("size")).unwrap();
+        let bad = text.replace("size 4 12", "size 3 12");
+        assert_eq!(bad.parse::<Replay>().err(), Some(ReplayError::Line(line + 1)));
+    }
+}
@@ -0,0 +1,44 @@
+//! Module isolating the seeded random number generator.
+
+/// Small seedable PRNG (xorshift64*).
+///
+/// Not cryptographic. Used where runs must be reproducible from a seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new (seed: u64) -> Self {
+        // Scramble the seed (splitmix64), so nearby seeds diverge and 0 is usable.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self { state: z.max(1) }
+    }
+
+    pub fn next_u64 (&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform integer in `0..n`.
+    pub fn below (&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// True with probability `p`.
+    pub fn chance (&mut self, p: f32) -> bool {
+        ((self.next_u64() >> 40) as f32) < p * (1 << 24) as f32
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T> (&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            v.swap(i, self.below(i + 1));
+        }
+    }
+}
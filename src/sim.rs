@@ -0,0 +1,220 @@
+//! Module isolating the single-player game simulator.
+//!
+//! Plays full games headlessly: pieces are dealt by a seeded 7-bag and placed by `solve()`.
+
+use std::time::{Duration, Instant};
+
+use super::{State, Move, Piece, Props, solve};
+use super::state::Garbage;
+use super::config::Config;
+use super::field::FIELD_H;
+use super::rng::Rng;
+use super::replay::Replay;
+
+/// Seeded 7-bag randomizer. Endless iterator over pieces.
+#[derive(Clone, Debug)]
+pub struct Bag {
+    rng: Rng,
+    bag: Vec<Piece>,
+}
+
+impl Bag {
+    pub fn new (seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            bag: Vec::with_capacity(7),
+        }
+    }
+}
+
+impl Iterator for Bag {
+    type Item = Piece;
+
+    fn next (&mut self) -> Option<Piece> {
+        if self.bag.is_empty() {
+            self.bag.extend(Piece::ALL);
+            self.rng.shuffle(&mut self.bag);
+        }
+        self.bag.pop()
+    }
+}
+
+/// Cumulative statistics of a game.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub pieces: u32,
+    pub lines: u32,
+    pub attack: u32,
+    /// Time spent choosing moves.
+    pub time: Duration,
+}
+
+impl Stats {
+    /// Pieces per second of thinking time.
+    pub fn pps (&self) -> f32 {
+        let t = self.time.as_secs_f32();
+        if t > 0.0 { self.pieces as f32 / t } else { 0.0 }
+    }
+
+    /// Attack per piece.
+    pub fn app (&self) -> f32 {
+        if self.pieces > 0 { self.attack as f32 / self.pieces as f32 } else { 0.0 }
+    }
+}
+
+/// Single-player game.
+pub struct Game {
+    pub state: State,
+    pub stats: Stats,
+    /// Set once the game has topped out.
+    pub over: bool,
+    /// Number of visible pieces after the current one.
+    pub preview: usize,
+    /// Record of every placement.
+    pub replay: Replay,
+    bag: Bag,
+}
+
+impl Game {
+    /// New game with a 5-piece preview.
+    pub fn new (seed: u64) -> Self {
+        Self::with_preview(seed, 5)
+    }
+
+    pub fn with_preview (seed: u64, preview: usize) -> Self {
+        let mut game = Self {
+            state: State::new(),
+            stats: Stats::default(),
+            over: false,
+            preview,
+            replay: Replay::seeded(seed, preview),
+            bag: Bag::new(seed),
+        };
+        game.refill();
+        game
+    }
+
+    /// Deals pieces until the current piece and the preview are filled.
+    fn refill (&mut self) {
+        while self.state.pieces.len() < self.preview + 1 {
+            let p = self.bag.next().unwrap();
+            self.state.push_piece(p);
+        }
+    }
+
+    /// Whether the current piece collides at its spawn position (block out).
+    pub fn spawn_blocked (&self) -> bool {
+        let mut cache = [[0; FIELD_H]; 4];
+        self.state.field.check_conflict(&mut cache, &self.state.field.spawn(), &self.state.pieces[0])
+    }
+
+    /// Locks a move, clearing lines and spawning the next piece.
+    ///
+    /// Returns the properties of the placement, or `None` if the move could not be placed.
+    pub fn play (&mut self, mov: &Move) -> Option<Props> {
+        if self.over {
+            return None;
+        }
+        self.state = self.state.play(mov)?;
+        let props = self.state.props;
+        self.replay.record(mov, &props);
+
+        self.stats.pieces += 1;
+        self.stats.lines += props.ds as u32;
+        self.stats.attack += props.atk as u32;
+
+        self.refill();
+        // Lock out & garbage top-out, then block out
+        self.over = props.top_out || self.spawn_blocked();
+        Some(props)
+    }
+
+    /// Queues garbage sent by an opponent, recording it in the replay.
+    pub fn queue_garbage (&mut self, garbage: Garbage) {
+        self.state.garbage.push_back(garbage);
+        self.replay.add_garbage(garbage);
+    }
+
+    /// Lets the bot choose and play a move. Ends the game if there is none.
+    pub fn step (&mut self, config: &Config) -> Option<Move> {
+        if self.over {
+            return None;
+        }
+        let start = Instant::now();
+        let res = solve(&self.state, &config.fit(&self.state));
+        self.stats.time += start.elapsed();
+
+        let Some((_, mov, _)) = res else {
+            self.over = true;
+            return None;
+        };
+        self.play(&mov)?;
+        Some(mov)
+    }
+
+    /// Plays until top-out or `max_pieces` placements.
+    pub fn run (&mut self, config: &Config, max_pieces: u32) -> &Stats {
+        while !self.over && self.stats.pieces < max_pieces {
+            self.step(config);
+        }
+        &self.stats
+    }
+}
+
+/// Plays a full game from `seed` and returns its statistics.
+pub fn simulate (seed: u64, config: &Config, max_pieces: u32) -> Stats {
+    let mut game = Game::new(seed);
+    game.run(config, max_pieces);
+    game.stats
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvaluatorMode;
+    use crate::field::{BUFFER_H, FIELD_H};
+
+    #[test]
+    fn sim_bag_test () {
+        let pieces: Vec<Piece> = Bag::new(7).take(70).collect();
+        for bag in pieces.chunks(7) {
+            for p in Piece::ALL {
+                assert!(bag.contains(&p));
+            }
+        }
+        assert_eq!(pieces, Bag::new(7).take(70).collect::<Vec<Piece>>());
+        assert_ne!(pieces, Bag::new(8).take(70).collect::<Vec<Piece>>());
+    }
+
+    #[test]
+    fn sim_game_test () {
+        let config = Config::new(1, EvaluatorMode::Norm);
+        let mut game = Game::new(42);
+        let stats = game.run(&config, 30).clone();
+
+        println!("{}", game.state);
+        println!("{:?}, pps: {:.2}, app: {:.2}", stats, stats.pps(), stats.app());
+        assert_eq!(stats.pieces, 30);
+        assert!(!game.over);
+        assert_eq!(game.state.pieces.len(), 6);
+
+        // Same seed, same game
+        let replay = simulate(42, &config, 30);
+        assert_eq!((replay.lines, replay.attack), (stats.lines, stats.attack));
+    }
+
+    #[test]
+    fn sim_top_out_test () {
+        let mut game = Game::new(0);
+        // Visible rows with staggered holes: at most the top one can be cleared
+        for y in BUFFER_H..FIELD_H {
+            game.state.field.m[y] = if y % 2 == 0 { 0b1_1_1_1_1_1_1_1_1_0 } else { 0b1_1_1_1_1_1_1_1_0_1 };
+        }
+        for _ in 0..5 {
+            game.step(&Config::new(0, EvaluatorMode::Norm));
+        }
+        assert!(game.over);
+        assert!(game.step(&Config::new(0, EvaluatorMode::Norm)).is_none());
+    }
+}
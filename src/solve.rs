@@ -1,24 +1,239 @@
 //! Module isolating `solve()` function.
 
-use super::{State, Field, Move, gen_moves, evaluate};
-use super::config::Config;
+use super::{State, Field, Move, Piece, Key, evaluate};
+use super::gen_moves::gen_moves_counted;
+use super::config::{Config, Cutoff, Search};
+use super::beam;
+use super::transposition::TranspositionTable;
+use super::state::bag_pieces;
+use super::rng::Rng;
+use super::stats::{Counters, SearchStats};
+use super::tree::Node;
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
 use rayon::prelude::*;
 
 const INHERITANCE_F: f32 = 0.0;
-const SCORE_CUTOFF_FACTOR: [f32; 3] = [0.4, 0.3, 0.25];
-const STRICT_CUTOFF: [usize; 3] = [12, 11, 10];
-static mut EXPANSIONS: u32 = 0;
+
+/// Line of play, see `solve_pv()`: per ply, the resultant State, the Move and its score.
+pub type Line = Vec<(State, Move, f32)>;
+
+/// Flag stopping an in-flight search from another thread. Clones share the flag.
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new () -> Self {
+        Self::default()
+    }
+
+    /// Stops the searches using this token. They return the best result found so far, see `solve_pv()`.
+    pub fn cancel (&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled (&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// Clears the flag, for the next search.
+    pub fn reset (&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Search budget of a solve, shared by all threads, along with its statistics.
+pub(crate) struct Limit {
+    deadline: Option<Instant>,
+    nodes: Option<u64>,
+    count: AtomicU64,
+    cancel: Option<CancelToken>,
+    pub(crate) stats: Arc<Counters>,
+}
+
+impl Limit {
+    pub(crate) fn new (configs: &Config) -> Self {
+        Self {
+            deadline: configs.time.map(|t| Instant::now() + t),
+            nodes: configs.nodes,
+            count: AtomicU64::new(0),
+            cancel: configs.cancel.clone(),
+            stats: Arc::new(Counters::new(configs.depth)),
+        }
+    }
+
+    /// Limit without budget, counting into the same statistics.
+    pub(crate) fn unbounded (&self) -> Self {
+        Self {
+            deadline: None,
+            nodes: None,
+            count: AtomicU64::new(0),
+            cancel: None,
+            stats: self.stats.clone(),
+        }
+    }
+
+    /// Counts an expanded node, returning whether the budget is exhausted or the search cancelled.
+    pub(crate) fn exceeded (&self) -> bool {
+        let n = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.cancel.as_ref().is_some_and(|c| c.is_cancelled())
+            || self.nodes.is_some_and(|max| n > max)
+            || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+}
+
+/// Root move ranked by `solve_multi()`.
+#[derive(Clone)]
+pub struct Candidate {
+    /// Resultant State of the move.
+    pub state: State,
+    pub mov: Move,
+    /// Inputs performing the move, see `Move::parse_list()`.
+    pub keys: Vec<Key>,
+    /// Searched score of the move.
+    pub score: f32,
+}
+
+/// Search aborted on an exhausted budget, or cancelled.
+struct Interrupted;
 
 /// Core function of Tetron. Produces an optimal move from input state.
 ///
 /// `depth` parameter configures DFS depth in exploration.
 /// `mode` parameter alters bot behavior & priority. Defaults to `Norm`. Used for topical testing. 
+/// With a time or node budget, deepens iteratively up to `depth`, see `solve_pv()`.
+/// `search` selects this pruned DFS or a beam search, see `beam.rs`.
 /// 
 /// Returns the selected Move, the resultant State, and the calculated score.
 /// Bot behavior configurable via source code. 
+/// See `solve_pv()` for the whole line of play behind the move.
 pub fn solve (state: &State, configs: &Config) -> Option<(State, Move, f32)> {
+    solve_deepening(state, configs).map(|(res, _)| res)
+}
+
+/// Anytime variant of `solve()`, see `solve_pv()`.
+pub fn solve_deepening (state: &State, configs: &Config) -> Option<((State, Move, f32), u8)> {
+    let (line, depth) = solve_pv(state, configs)?;
+    line.into_iter().next().map(|res| (res, depth))
+}
+
+/// Principal variation of `solve()`: the selected move followed by the line of play that justified it.
+///
+/// Every ply holds the resultant State, the Move and the score searched from that State, so the first
+/// ply is the result of `solve()`. The line stops short of `depth` where the queue runs out (chance
+/// nodes average over pieces rather than follow one line) or on a transposition table hit.
+///
+/// With a time or node budget, or a cancel token, searches depth 0, 1, ... up to `depth` until the
+/// budget runs out or the search is cancelled, see `CancelToken`.
+/// Returns the line of the deepest completed iteration, with that depth.
+/// Depth 0 always completes, so a move is found whenever one exists.
+/// Otherwise, directly searches at `depth`.
+/// A beam search needs no iterations: it stops at its last complete ply.
+pub fn solve_pv (state: &State, configs: &Config) -> Option<(Line, u8)> {
+    solve_stats(state, configs).0
+}
+
+/// `solve_pv()`, along with the statistics of the search.
+pub fn solve_stats (state: &State, configs: &Config) -> (Option<(Line, u8)>, SearchStats) {
+    solve_node(&mut Node::new(state.clone()), configs)
+}
+
+/// `solve_stats()` from the root of a search tree, see `SearchTree`.
+pub(crate) fn solve_node (root: &mut Node, configs: &Config) -> (Option<(Line, u8)>, SearchStats) {
+    let (res, stats) = deepen(root, configs, 1);
+    (res.map(|(mut lines, depth)| (lines.swap_remove(0), depth)), stats)
+}
+
+/// Ranked candidates of `solve()`: the `n` best root moves with their key sequences and searched
+/// scores, best first.
+///
+/// Each holds what `solve()` returns for the winner alone, along with the keys of its move.
+/// The DFS root keeps at least `n` children through pruning,
+/// so fewer are returned only when fewer moves exist. A beam search ranks the root moves that still
+/// have a state in the final beam. Returns an empty list on game over.
+pub fn solve_multi (state: &State, configs: &Config, n: usize) -> Vec<Candidate> {
+    deepen(&mut Node::new(state.clone()), configs, n.max(1)).0
+        .map_or(vec![], |(lines, _)| lines.into_iter()
+            .filter_map(|line| line.into_iter().next())
+            .map(|(state, mov, score)| Candidate { keys: mov.parse_list(), state, mov, score })
+            .collect())
+}
+
+/// Lines of the `n` best root moves, best first, with the depth reached, and the search statistics.
+/// See `solve_pv()`.
+fn deepen (root: &mut Node, configs: &Config, n: usize) -> (Option<(Vec<Line>, u8)>, SearchStats) {
+    let start = Instant::now();
+    let limit = Limit::new(configs);
+    let res = deepen_with(root, configs, n, &limit);
+    (res, limit.stats.snapshot(start.elapsed()))
+}
+
+fn deepen_with (root: &mut Node, configs: &Config, n: usize, limit: &Limit) -> Option<(Vec<Line>, u8)> {
+    if let Some(tt) = &configs.tt {
+        tt.new_search();
+    }
+    if let Search::Beam { width } = configs.search {
+        return beam::search(&root.state, configs, width, n, limit);
+    }
+    if !configs.budgeted() && configs.cancel.is_none() {
+        // Nothing can interrupt the search
+        let lines = search(root, configs, limit, n).unwrap_or_default();
+        return (!lines.is_empty()).then_some((lines, configs.depth));
+    }
+    let mut best = search(root, &Config { depth: 0, ..configs.clone() }, &limit.unbounded(), n).unwrap_or_default();
+    if best.is_empty() {
+        return None;
+    }
+    let mut reached: u8 = 0;
+
+    for depth in 1..=configs.depth {
+        match search(root, &Config { depth, ..configs.clone() }, limit, n) {
+            Ok(lines) if !lines.is_empty() => {
+                best = lines;
+                reached = depth;
+            },
+            Ok(_) | Err(Interrupted) => break,
+        }
+    }
+    Some((best, reached))
+}
+
+/// Searched score of `node`, with the line following it. Once its queue has run out, a chance node
+/// averages the scores over the pieces the bag could deal next (or a sample of `configs.chance_samples`
+/// of them), ending the line. Positions past a chance node are not kept in the tree.
+fn expect (node: &mut Node, configs: &Config, limit: &Limit) -> Result<(f32, Line), Interrupted> {
+    let state = &node.state;
+    if !state.pieces.is_empty() {
+        let line = search(node, configs, limit, 1)?.pop().unwrap_or_default();
+        return Ok((line.first().map_or(f32::NEG_INFINITY, |res| res.2), line));
+    }
+    let mut draws: Vec<Piece> = bag_pieces(state.bag).collect();
+    if let Some(n) = configs.chance_samples {
+        // Seeded by the position, so searches stay deterministic
+        let mut rng = Rng::new(TranspositionTable::key(state, configs.eval_mode));
+        rng.shuffle(&mut draws);
+        draws.truncate(n.max(1));
+    }
+
+    let mut sum: f32 = 0.0;
+    for p in draws.iter() {
+        let mut nstate = state.clone();
+        nstate.push_piece(*p);
+        sum += expect(&mut Node::new(nstate), configs, limit)?.0;
+    }
+    Ok((sum / draws.len() as f32, vec![]))
+}
+
+/// Depth-first search of `solve()` from `node`, aborting once `limit` is exceeded.
+///
+/// Children are generated on the first expansion of a node, and kept in the tree.
+/// Returns the lines of the `n` best children, best first. Empty on game over.
+fn search (node: &mut Node, configs: &Config, limit: &Limit, n: usize) -> Result<Vec<Line>, Interrupted> {
 
     // Benching
     let _bencher: Option<crate::Bencher> = if cfg!(feature = "bench") {
@@ -27,34 +242,59 @@ pub fn solve (state: &State, configs: &Config) -> Option<(State, Move, f32)> {
         }
     } else {None};   
 
-    let moves: HashMap<Field, Move> = gen_moves(state);
-    let mut queue: Vec<(State, Move, f32)> = vec![];
-    queue.reserve(moves.len());
+    if limit.exceeded() {
+        return Err(Interrupted);
+    }
+
+    limit.stats.node(configs.depth);
+    let state = &node.state;
+    let children = node.children.get_or_insert_with(|| {
+        let (moves, conflicts): (HashMap<Field, Move>, u64) = gen_moves_counted(state);
+        Counters::add(&limit.stats.moves, moves.len());
+        Counters::add(&limit.stats.conflicts, conflicts as usize);
+        moves.into_iter()
+            .map(|(field, mov)| {
+                let nstate = state.clone_as_child(field, &mov);
+                (mov, Node::new(nstate))
+            })
+            .collect()
+    });
+    Counters::add(&limit.stats.evaluations, children.len());
+    // Children, with the line searched past them
+    let mut queue: Vec<(&mut Node, &Move, f32, Line)> = Vec::with_capacity(children.len());
 
     // Evaluate all children
-    for (field, mov) in moves.iter() {
-        let nstate: State = state.clone_as_child(field.clone(), mov);
-        let score = evaluate(&nstate, configs.eval_mode);
-        queue.push((nstate, mov.clone(), score));
+    for (mov, child) in children.iter_mut() {
+        let score = evaluate(&child.state, configs.eval_mode);
+        queue.push((child, mov, score, vec![]));
     }
     // Sort reverse
     queue.sort_by(|a, b| a.2.total_cmp(&b.2));
 
 
-    // If empty (game over)
-    if queue.is_empty() {
-        return None;
-    }
+    // Best `n` children, each followed by its line
+    let best = |queue: Vec<(&mut Node, &Move, f32, Line)>| -> Vec<Line> {
+        queue.into_iter()
+            .rev()
+            .take(n)
+            .map(|(child, mov, score, next)| {
+                let mut line = vec![(child.state.clone(), mov.clone(), score)];
+                line.extend(next);
+                line
+            })
+            .collect()
+    };
 
-    // If no further expansion
-    if configs.depth == 0 {
-        return queue.pop()
+    // If no further expansion (or game over)
+    if configs.depth == 0 || queue.is_empty() {
+        return Ok(best(queue));
     }
 
     // Process Cutoff, dropping others.
     {
+        let Cutoff { factor, keep } = configs.cutoff(configs.depth);
         let score_variation = queue[queue.len()-1].2 - queue[0].2;
-        let cutoff_score: f32 = queue[queue.len()-1].2 - (score_variation * SCORE_CUTOFF_FACTOR[configs.depth as usize - 1]);
+        let cutoff_score: f32 = queue[queue.len()-1].2 - (score_variation * factor);
         let mut cutoff: usize = 0;
         for i in 0..queue.len() {
             if queue[i].2 > cutoff_score {
@@ -62,43 +302,60 @@ pub fn solve (state: &State, configs: &Config) -> Option<(State, Move, f32)> {
                 break;
             }
         }
-        cutoff = cutoff.max(queue.len() - queue.len().min(STRICT_CUTOFF[configs.depth as usize - 1] - 1));
-        queue.drain(0..(cutoff-1));
+        cutoff = cutoff.max(queue.len() - queue.len().min(keep.max(1) - 1));
+        // Candidates asked for are searched regardless
+        cutoff = cutoff.min((queue.len() + 1).saturating_sub(n));
+        Counters::add(&limit.stats.pruned, cutoff.saturating_sub(1));
+        queue.drain(0..cutoff.saturating_sub(1));
     }
     
     // Expand & Sort
     let next_configs = configs.next();
-    let func = |(nstate, _, score): &mut (State, Move, f32)| 
-            if let Some(res) = solve(&nstate, &next_configs) {
-                let nscore: f32 = *score * INHERITANCE_F + res.2 * (1.0 - INHERITANCE_F);
-                *score = nscore;
-            } else {
-                *score = f32::NEG_INFINITY;
+    let func = |(child, _, score, line): &mut (&mut Node, &Move, f32, Line)| -> Result<(), Interrupted> {
+            // Transposed positions reuse the stored score
+            let key = next_configs.tt.as_ref()
+                .map(|_| TranspositionTable::key(&child.state, next_configs.eval_mode));
+            let stored = next_configs.tt.as_ref().zip(key).and_then(|(tt, key)| tt.probe(key, next_configs.depth));
+            let child: f32 = match stored {
+                Some(child) => {
+                    Counters::add(&limit.stats.tt_hits, 1);
+                    child
+                },
+                None => {
+                    let (child, next) = expect(child, &next_configs, limit)?;
+                    if let Some((tt, key)) = next_configs.tt.as_ref().zip(key) {
+                        tt.store(key, next_configs.depth, child);
+                    }
+                    *line = next;
+                    child
+                },
             };
+            if child == f32::NEG_INFINITY {
+                *score = f32::NEG_INFINITY;
+            } else {
+                *score = *score * INHERITANCE_F + child * (1.0 - INHERITANCE_F);
+            }
+            Ok(())
+    };
 
-    if configs.depth == 2 {
+    if configs.depth >= 2 {
         queue.par_iter_mut()
-            .for_each(func);
+            .try_for_each(func)?;
     } else {
          queue.iter_mut()
-            .for_each(func);
+            .try_for_each(func)?;
     }
     queue.sort_by(|a, b| a.2.total_cmp(&b.2));
 
-    unsafe {
-        EXPANSIONS += queue.len() as u32;
-        if configs.depth == 3 {
-            EXPANSIONS = 0;
-        }
-    }
-    queue.pop()
+    Ok(best(queue))
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Instant;
+    use std::time::Duration;
+    use crate::field::BUFFER_H;
     use crate::{Piece, bench_increment_solve};
 
     #[test]
@@ -113,7 +370,7 @@ mod tests {
         state.pieces.push_back(Piece::S);
         //state.hold = Piece::J;
 
-        state.field.m = [   
+        state.field.m[BUFFER_H..].copy_from_slice(&[   
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
             0b0_0_0_0_0_0_0_0_0_0,
@@ -134,12 +391,13 @@ mod tests {
             0b0_0_0_0_0_0_0_0_0_0,
             0b1_1_0_0_0_0_0_0_0_0,
             0b1_1_0_0_0_0_0_0_0_0,
-        ];
+        ]);
 
         bench_increment_solve();
         let start = if cfg!(feature = "bench") { Some(Instant::now()) } else { None };
 
-        if let Some(out) = solve(&state, &Config::new(0, crate::evaluator::EvaluatorMode::Norm)) {
+        if let (Some((line, _)), stats) = solve_stats(&state, &Config::new(0, crate::evaluator::EvaluatorMode::Norm)) {
+            let out = &line[0];
             
             // Log out result
             println!("result score: \x1b[1m{}\x1b[0m", out.2);
@@ -147,9 +405,7 @@ mod tests {
             println!("move: {:?}", &out.1);
             println!("keys: {:?}", &out.1.parse_list());
             println!("prop: {:?}", &out.0.props);
-            unsafe {
-                println!("conflict computations: {}", crate::field::COUNTER);
-            }
+            println!("conflict computations: {}", stats.conflicts);
 
             // Time
             if let Some(time) = start {
@@ -161,4 +417,296 @@ mod tests {
         }
         crate::print_bench_result();
     }
+
+    #[test]
+    fn solve_deepening_test () {
+        let state: State = "
+            queue: TIOSZJ
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+        let configs = Config::new(2, crate::evaluator::EvaluatorMode::Norm);
+
+        // Ample budget completes every iteration, matching the fixed-depth search
+        let full = Config { nodes: Some(u64::MAX), ..configs.clone() };
+        let (res, depth) = solve_deepening(&state, &full).unwrap();
+        assert_eq!(depth, 2);
+        assert_eq!(res.1, solve(&state, &configs).unwrap().1);
+
+        // Exhausted budgets fall back to the deepest completed iteration
+        let (_, depth) = solve_deepening(&state, &Config { nodes: Some(1), ..configs.clone() }).unwrap();
+        assert_eq!(depth, 0);
+        let start = Instant::now();
+        let (res, depth) = solve_deepening(&state, &Config { time: Some(Duration::ZERO), ..configs.clone() }).unwrap();
+        println!("depth: {}, score: {}, dt: {}ms", depth, res.2, start.elapsed().as_millis());
+        assert_eq!(depth, 0);
+    }
+
+    #[test]
+    fn solve_depth_test () {
+        // Default schedule keeps the tuned pruning of depths 1 to 3
+        let legacy = [(0.4, 12), (0.3, 11), (0.25, 10)];
+        for (d, (factor, keep)) in legacy.into_iter().enumerate() {
+            assert_eq!(Cutoff::schedule(d as u8 + 1), Cutoff { factor, keep });
+        }
+        for d in 4..=20 {
+            let c = Cutoff::schedule(d);
+            assert!(c.factor >= 0.1 && c.keep >= 4);
+        }
+
+        // Beyond depth 3, with a narrow per-depth override
+        let state: State = "
+            queue: TIOSZJL
+            ##........
+            ###.....##
+        ".parse().unwrap();
+        let configs = Config {
+            cutoffs: vec![Cutoff { factor: 0.1, keep: 2 }; 5],
+            ..Config::new(5, crate::evaluator::EvaluatorMode::Norm)
+        };
+        let out = solve(&state, &configs.fit(&state)).unwrap();
+        println!("depth: {}, score: {}, move: {:?}", configs.fit(&state).depth, out.2, out.1);
+        assert_eq!(configs.fit(&state).depth, 5);
+    }
+
+    #[test]
+    fn solve_transposition_test () {
+        let state: State = "
+            queue: TIOSZJ  hold: L
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+        let tt = std::sync::Arc::new(TranspositionTable::new(1 << 16));
+        let configs = Config {
+            tt: Some(tt.clone()),
+            ..Config::new(2, crate::evaluator::EvaluatorMode::Norm)
+        };
+
+        let start = Instant::now();
+        let first = solve(&state, &configs).unwrap();
+        let dt_first = start.elapsed();
+        // Every expanded position of the first solve is stored, the second one is served from the table
+        let start = Instant::now();
+        let second = solve(&state, &configs).unwrap();
+        let dt_second = start.elapsed();
+        println!("first: {}ms, second: {}ms", dt_first.as_millis(), dt_second.as_millis());
+        assert_eq!((first.1, first.2), (second.1, second.2));
+
+        let plain = solve(&state, &Config::new(2, crate::evaluator::EvaluatorMode::Norm)).unwrap();
+        println!("plain: {}, table: {}", plain.2, first.2);
+    }
+
+    #[test]
+    fn solve_chance_test () {
+        let state: State = "
+            queue: TI
+            ##........
+            ###.....##
+        ".parse().unwrap();
+
+        // Deeper than the queue, lines are averaged over the possible next pieces
+        for samples in [None, Some(2)] {
+            let configs = Config {
+                chance_samples: samples,
+                ..Config::new(2, crate::evaluator::EvaluatorMode::Norm)
+            };
+            let start = Instant::now();
+            let out = solve(&state, &configs).unwrap();
+            println!("samples: {:?}, score: {}, dt: {}ms, move: {:?}", samples, out.2, start.elapsed().as_millis(), out.1);
+            assert!(out.2.is_finite());
+        }
+    }
+
+    #[test]
+    fn solve_pv_test () {
+        // Held O roofs the slot, for the T-spin double that follows
+        let state: State = "
+            queue: JTI  hold: O
+            .........#
+            #...######
+            ##.#######
+        ".parse().unwrap();
+        let configs = Config::new(2, crate::evaluator::EvaluatorMode::Norm);
+        let (line, depth) = solve_pv(&state, &configs).unwrap();
+        for (nstate, mov, score) in line.iter() {
+            println!("score: {}, move: {:?}", score, mov);
+            println!("{}", nstate);
+        }
+        assert_eq!(depth, 2);
+        assert_eq!(line.len(), 3);
+
+        // The line starts with the move of `solve()`, and plays out from the root
+        let out = solve(&state, &configs).unwrap();
+        assert_eq!(line[0].1, out.1);
+        assert_eq!(line[0].2, out.2);
+        let mut prev = state.clone();
+        for (nstate, mov, _) in line.iter() {
+            assert_eq!(prev.play(mov).unwrap().field, nstate.field);
+            prev = nstate.clone();
+        }
+
+        assert!(line[0].1.hold);
+        assert!(line[1].1.tspin);
+        assert_eq!(line[1].0.props.ds, 2);
+
+        // Beam search reports its line as well
+        let beam = Config { search: Search::Beam { width: 16 }, ..configs.clone() };
+        let (line, _) = solve_pv(&state, &beam).unwrap();
+        assert_eq!(line.len(), 3);
+    }
+
+    #[test]
+    fn solve_multi_test () {
+        let state: State = "
+            queue: TIOSZJ
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+
+        for configs in [
+            Config::new(2, crate::evaluator::EvaluatorMode::Norm),
+            Config { search: Search::Beam { width: 32 }, ..Config::new(2, crate::evaluator::EvaluatorMode::Norm) },
+        ] {
+            let best = solve(&state, &configs).unwrap();
+            let candidates = solve_multi(&state, &configs, 5);
+            for c in candidates.iter() {
+                println!("score: {}, keys: {:?}", c.score, c.keys);
+                assert_eq!(c.keys, c.mov.parse_list());
+            }
+            assert_eq!(candidates.len(), 5);
+
+            // Ranked, the winner first, over distinct root moves
+            assert_eq!(candidates[0].mov, best.1);
+            assert_eq!(candidates[0].score, best.2);
+            assert!(candidates.windows(2).all(|w| w[0].score >= w[1].score));
+            for (i, a) in candidates.iter().enumerate() {
+                assert!(candidates[i+1..].iter().all(|b| b.state.field != a.state.field || b.state.hold != a.state.hold));
+            }
+        }
+
+        // More candidates than the pruning keeps, all searched
+        let configs = Config {
+            cutoffs: vec![Cutoff { factor: 0.0, keep: 1 }; 2],
+            ..Config::new(2, crate::evaluator::EvaluatorMode::Norm)
+        };
+        let candidates = solve_multi(&state, &configs, 8);
+        assert_eq!(candidates.len(), 8);
+        assert!(candidates.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn solve_cancel_test () {
+        let state: State = "
+            queue: TIOSZJLT
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+        let cancel = CancelToken::new();
+        let configs = Config {
+            cancel: Some(cancel.clone()),
+            ..Config::new(6, crate::evaluator::EvaluatorMode::Norm)
+        };
+
+        // Cancelled beforehand, only depth 0 completes
+        cancel.cancel();
+        let (_, depth) = solve_deepening(&state, &configs).unwrap();
+        assert_eq!(depth, 0);
+        cancel.reset();
+
+        // Left to complete, deepens to the same move as an uncancellable search
+        let shallow = Config { depth: 2, ..configs.clone() };
+        let (res, depth) = solve_deepening(&state, &shallow).unwrap();
+        assert_eq!(depth, 2);
+        assert_eq!(res.1, solve(&state, &Config { cancel: None, ..shallow.clone() }).unwrap().1);
+
+        // Cancelled midway, from another thread
+        for configs in [configs.clone(), Config { nodes: Some(u64::MAX), ..configs.clone() }] {
+            let start = Instant::now();
+            let (res, depth) = std::thread::scope(|s| {
+                s.spawn(|| {
+                    std::thread::sleep(Duration::from_millis(100));
+                    cancel.cancel();
+                });
+                solve_deepening(&state, &configs).unwrap()
+            });
+            println!("depth: {}, score: {}, dt: {}ms", depth, res.2, start.elapsed().as_millis());
+            assert!(depth < 6);
+            assert!(start.elapsed() < Duration::from_secs(5));
+            cancel.reset();
+        }
+    }
+
+    #[test]
+    fn solve_stats_test () {
+        let state: State = "
+            queue: TIOSZJ
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+
+        let configs = Config::new(2, crate::evaluator::EvaluatorMode::Norm);
+        let (res, stats) = solve_stats(&state, &configs);
+        println!("{:?}", stats);
+        assert!(res.is_some());
+        // One root, fanning out
+        assert_eq!(stats.nodes.len(), 3);
+        assert_eq!(stats.nodes[2], 1);
+        assert!(stats.nodes[1] > 1 && stats.nodes[0] > stats.nodes[1]);
+        assert_eq!(stats.evaluations, stats.moves);
+        assert!(stats.pruned > 0 && stats.conflicts > 0);
+        assert_eq!(stats.tt_hits, 0);
+        // Parallel counts don't race
+        assert_eq!(solve_stats(&state, &configs).1.nodes, stats.nodes);
+
+        let tt = Arc::new(TranspositionTable::new(1 << 16));
+        let configs = Config { tt: Some(tt), ..configs.clone() };
+        solve_stats(&state, &configs);
+        let (_, stats) = solve_stats(&state, &configs);
+        assert!(stats.tt_hits > 0);
+        assert!(stats.total_nodes() < 3);
+
+        let beam = Config { search: Search::Beam { width: 8 }, ..Config::new(2, crate::evaluator::EvaluatorMode::Norm) };
+        let (_, stats) = solve_stats(&state, &beam);
+        println!("{:?}", stats);
+        assert_eq!(stats.nodes[2], 1);
+        assert!(stats.nodes[1] <= 8 && stats.nodes[0] <= 8);
+    }
+
+    #[test]
+    fn solve_size_test () {
+        let config = Config::new(1, crate::evaluator::EvaluatorMode::Norm);
+
+        // 4-wide combo trainer, 12-wide board & shortened board
+        let mut combo: State = "
+            queue: IOTLJSZ
+            ....
+            #..#
+            ##.#
+        ".parse().unwrap();
+        combo.props.combo = 12;
+        let wide: State = "
+            queue: TIOSZJ
+            ............
+            ##.....#####
+            ###.########
+        ".parse().unwrap();
+        let mut short = State::new();
+        short.field = Field::with_size(10, 4);
+        short.pieces.extend([Piece::T, Piece::I, Piece::O, Piece::S]);
+
+        for mut state in [combo, wide, short] {
+            for _ in 0..3 {
+                let (_, mov, score) = solve(&state, &config).unwrap();
+                state = state.play(&mov).unwrap();
+                println!("{}\nscore: {}", state, score);
+                assert!(state.field.m.iter().all(|&row| row <= state.field.full_row()));
+                assert!(state.field.m[..state.field.ceiling()].iter().all(|&row| row == 0));
+            }
+        }
+    }
 }
@@ -1,6 +1,61 @@
 use std::collections::VecDeque;
 
 use super::{Props, Field, Piece, Move};
+use super::field::FIELD_H;
+
+/// Bitmask of a full 7-bag, bit `p as u8` per piece.
+pub const FULL_BAG: u8 = (1 << 7) - 1;
+
+/// Pieces of a bag bitmask.
+pub fn bag_pieces (bag: u8) -> impl Iterator<Item = Piece> {
+    Piece::ALL.into_iter().filter(move |p| bag & (1 << *p as u8) > 0)
+}
+
+/// Bag left after dealing `p`, refilled once empty.
+pub fn bag_draw (bag: u8, p: Piece) -> u8 {
+    let bag = bag & !(1 << p as u8);
+    if bag == 0 {FULL_BAG} else {bag}
+}
+
+/// Infers the pieces left in the current bag after a known piece sequence.
+///
+/// Bags may start at any alignment under which no bag deals a piece twice, so the pieces left
+/// under each valid alignment are merged. Pieces of a bag started before the sequence are unknown,
+/// so they are left in it.
+pub fn infer_bag (pieces: &[Piece]) -> u8 {
+    let mut union: u8 = 0;
+    for start in 0..7 {
+        let mut bag: u8 = FULL_BAG;
+        let mut valid = true;
+        for (i, p) in pieces.iter().enumerate() {
+            if i >= start && (i - start) % 7 == 0 {
+                bag = FULL_BAG;
+            }
+            let bit = 1 << *p as u8;
+            if bag & bit == 0 {
+                valid = false;
+                break;
+            }
+            bag &= !bit;
+        }
+        if valid {
+            // A bag ending with the sequence leaves a new one
+            let ends = pieces.len() >= start && (pieces.len() - start).is_multiple_of(7);
+            union |= if ends || bag == 0 {FULL_BAG} else {bag};
+        }
+    }
+    if union == 0 {FULL_BAG} else {union}
+}
+
+/// A garbage batch waiting to enter the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Garbage {
+    pub lines: u8,
+    /// Column of the hole in every row of the batch.
+    pub hole: u8,
+    /// Placements the batch waits before it can enter the board: 0 enters on the next placement.
+    pub delay: u8,
+}
 
 /// Structure representing any possible 'state' of the game.
 ///
@@ -11,6 +66,10 @@ pub struct State {
     pub hold: Piece,
     pub field: Field,
     pub props: Props,
+    /// Pieces the current 7-bag can still deal after the queue, bit `p as u8` per piece.
+    pub bag: u8,
+    /// Garbage sent by the opponent and not yet on the board, oldest first.
+    pub garbage: VecDeque<Garbage>,
 }
 impl State {
     /// Spawns new `State` instance.
@@ -22,14 +81,83 @@ impl State {
             pieces,
             hold: Piece::None,
             field: Field::new(),
-            props: Props::new()
+            props: Props::new(),
+            bag: FULL_BAG,
+            garbage: VecDeque::new(),
+        }
+    }
+
+    /// Pending garbage lines.
+    pub fn incoming (&self) -> u8 {
+        self.garbage.iter().map(|g| g.lines).sum()
+    }
+
+    /// Cancels pending garbage with an attack, returning the attack left to send.
+    pub fn cancel_garbage (&mut self, mut atk: u8) -> u8 {
+        while atk > 0 {
+            let Some(g) = self.garbage.front_mut() else { break };
+            let n = atk.min(g.lines);
+            g.lines -= n;
+            atk -= n;
+            if g.lines == 0 {
+                self.garbage.pop_front();
+            }
+        }
+        atk
+    }
+
+    /// Processes the garbage queue after a placement.
+    ///
+    /// The placement's attack cancels pending garbage first. Due garbage then enters the board,
+    /// only on placements that clear no line, and the delays of the rest tick down.
+    fn receive_garbage (&mut self) {
+        self.cancel_garbage(self.props.atk);
+        if self.props.ds == 0 {
+            while self.garbage.front().is_some_and(|g| g.delay == 0) {
+                let g = self.garbage.pop_front().unwrap();
+                self.props.garbage = self.props.garbage.saturating_add(g.lines);
+                self.props.top_out |= self.field.push_garbage(g.lines as usize, g.hole as usize);
+            }
+        }
+        for g in self.garbage.iter_mut() {
+            g.delay = g.delay.saturating_sub(1);
+        }
+    }
+
+    /// Appends a piece to the queue, drawing it from the bag.
+    pub fn push_piece (&mut self, p: Piece) {
+        self.pieces.push_back(p);
+        self.bag = bag_draw(self.bag, p);
+    }
+
+    /// Seeds the bag from the number of pieces the game has dealt so far, the queue included.
+    ///
+    /// Bags start every 7 pieces. Pieces of the current bag dealt before the queue are unknown,
+    /// so they are left in it.
+    pub fn seed_bag (&mut self, dealt: usize) {
+        let k = dealt % 7;
+        self.bag = FULL_BAG;
+        for p in self.pieces.iter().rev().take(k) {
+            self.bag &= !(1 << *p as u8);
         }
+        if k == 0 || self.bag == 0 {
+            self.bag = FULL_BAG;
+        }
+    }
+
+    /// Seeds the bag from the queue alone, see `infer_bag()`.
+    pub fn infer_bag (&mut self) {
+        let pieces: Vec<Piece> = self.pieces.iter().copied().collect();
+        self.bag = infer_bag(&pieces);
     }
 
     /// Clones a 'child' to the state. 
     ///
     /// Sets particular attributes according to the property inheritance policies.
     /// Used to spawn a child state to set a piece placement on.
+    /// Consuming queue pieces leaves the bag after the queue unchanged, so it is inherited.
+    /// Pending garbage is inherited, then cancelled or received according to the placement.
+    /// A piece locked entirely inside the hidden buffer locks out.
     pub fn clone_as_child (&self, mut field: Field, mov: &Move) -> State {
         let mut props: Props = Props { 
             sum_atk: self.props.sum_atk + self.props.atk,
@@ -39,9 +167,15 @@ impl State {
             atk: 0,
             ds: 0,
             clears: 0,
+            garbage: 0,
+            top_out: false,
             ..self.props
         };
         
+        // Lock out: the placement left the visible board untouched
+        let visible = field.visible_top();
+        props.top_out = field.m[visible..] == self.field.m[visible..];
+
         // process field and edit properties.
         field.set_props(mov, &mut props);
 
@@ -57,20 +191,69 @@ impl State {
             pieces.pop_front();
         }
 
-        Self {
+        let mut child = Self {
             field,
             pieces,
             hold,
             props,
+            bag: self.bag,
+            garbage: self.garbage.clone(),
+        };
+        child.receive_garbage();
+        child
+    }
+
+    /// Piece that would be swapped in on hold: the hold piece, or the next piece if hold is empty.
+    pub fn hold_piece (&self) -> Piece {
+        if self.hold == Piece::None {
+            self.pieces.get(1).copied().unwrap_or(Piece::None)
+        } else {
+            self.hold
         }
     }
+
+    /// Piece placed by the given move.
+    pub fn placed_piece (&self, mov: &Move) -> Piece {
+        if mov.hold {
+            self.hold_piece()
+        } else {
+            self.pieces.front().copied().unwrap_or(Piece::None)
+        }
+    }
+
+    /// Plays a locked move, returning the resulting child state.
+    ///
+    /// Returns `None` if the queue is empty, or the move has an invalid rotation, lies off the
+    /// board or overlaps filled cells.
+    pub fn play (&self, mov: &Move) -> Option<State> {
+        let piece = *self.pieces.front()?;
+        let placed = self.placed_piece(mov);
+        if placed == Piece::None || mov.r > 3 {
+            return None;
+        }
+        if self.field.check_conflict(&mut [[0; FIELD_H]; 4], mov, &placed) {
+            return None;
+        }
+        let field = self.field.apply_move(mov, &piece, &self.hold_piece()).ok()?;
+        Some(self.clone_as_child(field, mov))
+    }
+
+    /// Whether `other` is the same position: field, hold, props and pending garbage. The queues
+    /// are not compared, as searches may sample pieces past the known ones.
+    pub fn same_position (&self, other: &State) -> bool {
+        self.field == other.field && self.hold == other.hold && self.props == other.props && self.garbage == other.garbage
+    }
 }
 
 use std::fmt;
+use std::str::FromStr;
+use crate::field::{parse_diagram, ParseError};
+
 impl fmt::Display for State {
     fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result { 
-        for y in 0..20 {
-            for x in 0..10 {
+        let top = self.field.top();
+        for y in top..FIELD_H {
+            for x in 0..self.field.width {
                 let b: bool = (self.field.m[y] & (1 << x)) >> x == 1;
                 if b {
                     write!(f, "# ")?;
@@ -78,14 +261,14 @@ impl fmt::Display for State {
                     write!(f, ". ")?;
                 }
             }
-            print!(" ");
-            match y {
+            write!(f, " ")?;
+            match y - top {
                 0 => write!(f, "b2b:   {:>2}", self.props.b2b)?,
                 1 => write!(f, "combo: {:>2}", self.props.combo)?,
                 3 => write!(f, "hold:  {:?}", self.hold)?,
                 4 => write!(f, "queue:")?,
-                5..=9 => if self.pieces.len() > y-5 {
-                    write!(f, "{:?}", self.pieces[y-5])?
+                i @ 5..=9 => if self.pieces.len() > i-5 {
+                    write!(f, "{:?}", self.pieces[i-5])?
                 },
                 _ => ()
             };
@@ -95,3 +278,229 @@ impl fmt::Display for State {
         Ok(())
     }
 }
+
+impl FromStr for State {
+    type Err = ParseError;
+
+    /// Parses the diagram produced by `Display`, or a compact variant with a header above the grid:
+    /// ```text
+    /// queue: TIOSZ  hold: L  b2b: 1
+    /// ....##....
+    /// #########.
+    /// ```
+    /// Entries are `b2b:`, `combo:`, `hold:` and `queue:`. Queue pieces may be listed one per line.
+    fn from_str (s: &str) -> Result<Self, Self::Err> {
+        let (field, text) = parse_diagram(s)?;
+        let mut state = State::new();
+        state.field = field;
+
+        let mut key: &str = "";
+        for token in text.iter().flat_map(|t| t.split_whitespace()) {
+            if let Some(k) = token.strip_suffix(':') {
+                key = k;
+                continue;
+            }
+            let err = || ParseError::Value(token.to_string());
+            match key {
+                "b2b" => state.props.b2b = token.parse().map_err(|_| err())?,
+                "combo" => state.props.combo = token.parse().map_err(|_| err())?,
+                "hold" => state.hold = match token {
+                    "None" | "-" => Piece::None,
+                    _ if token.len() == 1 => token.chars().next().and_then(Piece::from_char).ok_or_else(err)?,
+                    _ => return Err(err()),
+                },
+                "queue" => for c in token.chars() {
+                    state.pieces.push_back(Piece::from_char(c).ok_or_else(err)?);
+                },
+                _ => return Err(err()),
+            }
+        }
+        state.infer_bag();
+        Ok(state)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::BUFFER_H;
+
+    #[test]
+    fn state_parse_test () {
+        let mut state = State::new();
+        state.field.m[38] = 0b0_0_0_0_0_1_1_1_1_1;
+        state.field.m[39] = 0b0_1_1_1_1_1_1_1_1_1;
+        state.pieces.extend([Piece::T, Piece::I, Piece::O]);
+        state.hold = Piece::L;
+        state.props.b2b = 2;
+        state.props.combo = 1;
+
+        let parsed: State = state.to_string().parse().unwrap();
+        assert_eq!(parsed.field, state.field);
+        assert_eq!(parsed.pieces, state.pieces);
+        assert_eq!(parsed.hold, state.hold);
+        assert_eq!((parsed.props.b2b, parsed.props.combo), (2, 1));
+
+        // Stacks reaching into the buffer are shown in full
+        let mut tall = state.clone();
+        tall.field.m[12] = 0b0_0_0_0_0_0_0_0_0_1;
+        assert_eq!(tall.to_string().lines().count(), 29);
+        assert_eq!(tall.to_string().parse::<State>().unwrap().field, tall.field);
+
+        let parsed: State = "
+            queue: TIO  hold: L  b2b: 2 combo: 1
+            #####.....
+            #########.
+        ".parse().unwrap();
+        assert_eq!(parsed.field, state.field);
+        assert_eq!(parsed.pieces, state.pieces);
+        assert_eq!(parsed.hold, state.hold);
+        assert_eq!((parsed.props.b2b, parsed.props.combo), (2, 1));
+
+        // Long b2b chains parse and can be searched from
+        let chain: State = "
+            queue: IOTLJ  b2b: 4
+            #########.
+            #########.
+            #########.
+            #########.
+        ".parse().unwrap();
+        assert_eq!(chain.props.b2b, 4);
+        assert!(crate::solve(&chain, &crate::config::Config::new(0, crate::EvaluatorMode::Norm)).is_some());
+
+        assert!(State::new().to_string().parse::<State>().unwrap().pieces.is_empty());
+        assert_eq!("queue: TX\n..........".parse::<State>().err(), Some(ParseError::Value("TX".to_string())));
+        assert_eq!("level: 3".parse::<State>().err(), Some(ParseError::Value("3".to_string())));
+    }
+
+    #[test]
+    fn state_bag_test () {
+        use Piece::*;
+        assert_eq!(infer_bag(&[]), FULL_BAG);
+        // Short sequences fit every alignment, including a bag ending right after them
+        assert_eq!(infer_bag(&[T, I, O]), FULL_BAG);
+        assert_eq!(infer_bag(&[Z, I, O, J, L]), FULL_BAG);
+        // Alignments merge: the second Z always falls in the current bag, so only Z is ruled out
+        assert_eq!(infer_bag(&[Z, I, O, J, L, Z]), FULL_BAG & !(1 << Z as u8));
+        // A repeated piece starts a new bag
+        assert_eq!(infer_bag(&[J, L, S, Z, T, I, O, T]), FULL_BAG & !(1 << T as u8));
+        assert_eq!(infer_bag(&[T, T]), FULL_BAG & !(1 << T as u8));
+
+        let mut state = State::new();
+        for p in [J, L, S, Z, T, I, O, T, S] {
+            state.push_piece(p);
+        }
+        assert_eq!(bag_pieces(state.bag).collect::<Vec<Piece>>(), vec![J, L, Z, I, O]);
+
+        // Two pieces into the current bag, of which the queue shows the last one
+        state.pieces.drain(..8);
+        state.seed_bag(9);
+        assert_eq!(state.bag, FULL_BAG & !(1 << S as u8));
+        state.seed_bag(14);
+        assert_eq!(state.bag, FULL_BAG);
+
+        // Inherited by children
+        let child = state.clone_as_child(state.field.clone(), &Move::new());
+        assert_eq!(child.bag, state.bag);
+    }
+
+    #[test]
+    fn state_garbage_test () {
+        let mut state: State = "
+            queue: IIII
+            ..........
+            ####.#####
+        ".parse().unwrap();
+        state.garbage.extend([
+            Garbage { lines: 1, hole: 0, delay: 0 },
+            Garbage { lines: 2, hole: 9, delay: 1 },
+        ]);
+        assert_eq!(state.incoming(), 3);
+
+        // Cancelling takes from the oldest batch first
+        let mut cancel = state.clone();
+        assert_eq!(cancel.cancel_garbage(2), 0);
+        assert_eq!(cancel.garbage, [Garbage { lines: 1, hole: 9, delay: 1 }]);
+        assert_eq!(cancel.cancel_garbage(3), 2);
+        assert!(cancel.garbage.is_empty());
+
+        // A clearing placement holds due garbage back, while delays still tick
+        let clear = Move { x: 4, y: 37, r: 1, ..Move::new() };
+        let child = state.play(&clear).unwrap();
+        println!("{}", child);
+        assert_eq!((child.props.ds, child.props.garbage), (1, 0));
+        assert_eq!(child.field.m[39], 0b0_0_0_0_0_1_0_0_0_0);
+        assert_eq!(child.incoming(), 3);
+        assert!(child.garbage.iter().all(|g| g.delay == 0));
+
+        // The next placement receives everything due
+        let delayed = child.clone();
+        let child = child.play(&Move { x: 0, y: 37, r: 1, ..Move::new() }).unwrap();
+        println!("{}", child);
+        assert_eq!(child.props.garbage, 3);
+        assert!(!child.props.top_out);
+        assert!(child.garbage.is_empty());
+        assert_eq!(child.field.m[36], 0b0_0_0_0_0_1_0_0_0_1);
+        assert_eq!(child.field.m[37], 0b1_1_1_1_1_1_1_1_1_0);
+        assert_eq!(child.field.m[38], 0b0_1_1_1_1_1_1_1_1_1);
+        assert_eq!(child.field.m[39], 0b0_1_1_1_1_1_1_1_1_1);
+
+        // A delay holds garbage back for as many placements
+        let mut state = delayed;
+        state.garbage = [Garbage { lines: 1, hole: 0, delay: 1 }].into();
+        let child = state.play(&Move { x: 0, y: 37, r: 1, ..Move::new() }).unwrap();
+        assert_eq!((child.props.garbage, child.incoming()), (0, 1));
+        let child = child.play(&Move { x: 9, y: 37, r: 1, ..Move::new() }).unwrap();
+        assert_eq!((child.props.garbage, child.incoming()), (1, 0));
+
+        // Garbage pushing the stack off the top of the buffer tops out
+        let drop = Move { x: 5, y: 30, r: 0, ..Move::new() };
+        let mut state = child.clone();
+        state.garbage.push_back(Garbage { lines: 20, hole: 0, delay: 0 });
+        assert!(!state.play(&drop).unwrap().props.top_out);
+        state.garbage[0].lines = 40;
+        let child = state.play(&drop).unwrap();
+        assert!(child.props.top_out);
+        assert_eq!(crate::evaluator::evaluate(&child, crate::EvaluatorMode::Norm), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn state_lock_out_test () {
+        let state: State = "
+            queue: OO
+            ..........
+        ".parse().unwrap();
+
+        // Locking partly inside the visible board is fine
+        let child = state.play(&Move { x: 4, y: 39, r: 0, ..Move::new() }).unwrap();
+        assert!(!child.props.top_out);
+
+        // Locking entirely inside the buffer locks out, even at spawn
+        let child = state.play(&Move::new()).unwrap();
+        println!("{}", child);
+        assert!(child.props.top_out);
+        let mut state = state.clone();
+        state.field.m[BUFFER_H..].fill(0b0_0_0_0_0_0_0_1_1_1);
+        assert!(!state.play(&Move { x: 7, y: BUFFER_H as i8, r: 0, ..Move::new() }).unwrap().props.top_out);
+        assert!(state.play(&Move { x: 1, y: BUFFER_H as i8 - 1, r: 0, ..Move::new() }).unwrap().props.top_out);
+    }
+
+    #[test]
+    fn state_play_invalid_test () {
+        let state: State = "
+            queue: T
+            ##########
+            .#########
+        ".parse().unwrap();
+
+        // Off the board, bad rotations & overlapping placements are rejected
+        assert!(state.play(&Move { x: 0, y: 30, ..Move::new() }).is_none());
+        assert!(state.play(&Move { x: 4, y: 41, ..Move::new() }).is_none());
+        assert!(state.play(&Move { r: 4, ..Move::new() }).is_none());
+        assert!(state.play(&Move { x: 4, y: 39, ..Move::new() }).is_none());
+        assert!(state.play(&Move { hold: true, ..Move::new() }).is_none());
+        assert!(State::new().play(&Move::new()).is_none());
+        assert!(state.play(&Move { x: 1, y: 37, ..Move::new() }).is_some());
+    }
+}
@@ -0,0 +1,76 @@
+//! Module isolating the statistics of a search.
+//!
+//! Counters are atomics shared by the search threads, read once the solve is done.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Statistics of a solve, summed over all threads and deepening iterations.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// Nodes expanded by remaining depth: `nodes[d]` counts the states searched with `d` plies left.
+    pub nodes: Vec<u64>,
+    /// Moves generated over all expanded nodes, excluding those a reused `SearchTree` already held.
+    pub moves: u64,
+    /// States evaluated.
+    pub evaluations: u64,
+    /// Children dropped by the cutoffs.
+    pub pruned: u64,
+    /// Piece conflicts computed by the move generator, past its per-node cache.
+    pub conflicts: u64,
+    /// Transposition table hits.
+    pub tt_hits: u64,
+    /// Wall time of the solve.
+    pub time: Duration,
+}
+
+impl SearchStats {
+    /// Nodes expanded at any depth.
+    pub fn total_nodes (&self) -> u64 {
+        self.nodes.iter().sum()
+    }
+}
+
+/// Counters behind `SearchStats`.
+#[derive(Default)]
+pub(crate) struct Counters {
+    nodes: Vec<AtomicU64>,
+    pub(crate) moves: AtomicU64,
+    pub(crate) evaluations: AtomicU64,
+    pub(crate) pruned: AtomicU64,
+    pub(crate) conflicts: AtomicU64,
+    pub(crate) tt_hits: AtomicU64,
+}
+
+impl Counters {
+    /// Counters of a search up to `depth` plies.
+    pub(crate) fn new (depth: u8) -> Self {
+        Self {
+            nodes: (0..=depth).map(|_| AtomicU64::new(0)).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Counts a node expanded with `depth` plies left.
+    pub(crate) fn node (&self, depth: u8) {
+        if let Some(n) = self.nodes.get(depth as usize) {
+            n.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add (counter: &AtomicU64, n: usize) {
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot (&self, time: Duration) -> SearchStats {
+        SearchStats {
+            nodes: self.nodes.iter().map(|n| n.load(Ordering::Relaxed)).collect(),
+            moves: self.moves.load(Ordering::Relaxed),
+            evaluations: self.evaluations.load(Ordering::Relaxed),
+            pruned: self.pruned.load(Ordering::Relaxed),
+            conflicts: self.conflicts.load(Ordering::Relaxed),
+            tt_hits: self.tt_hits.load(Ordering::Relaxed),
+            time,
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Module implementing the Tetris Bot Protocol (TBP) front-end.
+//!
+//! Messages are exchanged as JSON lines. See the `tetron-tbp` binary for the stdin/stdout loop.
+//! Coordinates follow the TBP convention: `y = 0` is the bottom row, orientations are SRS.
+
+use serde::{Deserialize, Serialize};
+
+use super::{State, Move, Piece, solve, gen_moves};
+use super::config::Config;
+use super::field;
+
+/// Height of a `Field`, hidden buffer included. TBP boards have 40 rows as well.
+const FIELD_H: i32 = field::FIELD_H as i32;
+
+/// Piece type as named by TBP.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TbpPiece { I, O, T, L, J, S, Z }
+
+impl From<TbpPiece> for Piece {
+    fn from (p: TbpPiece) -> Piece {
+        match p {
+            TbpPiece::I => Piece::I,
+            TbpPiece::O => Piece::O,
+            TbpPiece::T => Piece::T,
+            TbpPiece::L => Piece::L,
+            TbpPiece::J => Piece::J,
+            TbpPiece::S => Piece::S,
+            TbpPiece::Z => Piece::Z,
+        }
+    }
+}
+
+impl TbpPiece {
+    fn from_piece (p: Piece) -> Option<Self> {
+        match p {
+            Piece::I => Some(TbpPiece::I),
+            Piece::O => Some(TbpPiece::O),
+            Piece::T => Some(TbpPiece::T),
+            Piece::L => Some(TbpPiece::L),
+            Piece::J => Some(TbpPiece::J),
+            Piece::S => Some(TbpPiece::S),
+            Piece::Z => Some(TbpPiece::Z),
+            Piece::None => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation { North, East, South, West }
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin { None, Mini, Full }
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub kind: TbpPiece,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct TbpMove {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Start {
+    pub hold: Option<TbpPiece>,
+    pub queue: Vec<TbpPiece>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    /// Rows from the bottom up. `None` is an empty cell.
+    pub board: Vec<Vec<Option<String>>>,
+}
+
+/// Messages sent by the frontend.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules {},
+    Start(Start),
+    Stop {},
+    Suggest {},
+    Play {
+        #[serde(rename = "move")]
+        mov: TbpMove,
+    },
+    NewPiece { piece: TbpPiece },
+    Quit {},
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MoveInfo {
+    pub nodes: u64,
+    pub nps: f64,
+    pub extra: String,
+}
+
+/// Messages sent by the bot.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Ready {},
+    Error { reason: String },
+    Suggestion {
+        moves: Vec<TbpMove>,
+        move_info: MoveInfo,
+    },
+}
+
+impl BotMessage {
+    /// The `info` message announced on startup.
+    pub fn info () -> Self {
+        BotMessage::Info {
+            name: "Tetron".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            author: "PaddingProductions".to_string(),
+            features: vec![],
+        }
+    }
+}
+
+impl Start {
+    /// Builds the `State` described by a `start` message.
+    ///
+    /// Rows above the representable board are dropped.
+    pub fn to_state (&self) -> State {
+        let mut state = State::new();
+        for (y, row) in self.board.iter().enumerate().take(FIELD_H as usize) {
+            for (x, cell) in row.iter().enumerate().take(field::FIELD_W) {
+                if cell.is_some() {
+                    state.field.m[FIELD_H as usize - 1 - y] |= 1 << x;
+                }
+            }
+        }
+        state.hold = self.hold.map_or(Piece::None, Piece::from);
+        state.pieces.extend(self.queue.iter().map(|&p| Piece::from(p)));
+        state.infer_bag();
+        state.props.b2b = if self.back_to_back {1} else {0};
+        state.props.combo = self.combo.min(u8::MAX as u32) as u8;
+        state
+    }
+}
+
+/// Offset from a `Move`'s center to the TBP center, in `Move` coordinates.
+///
+/// Tetron's south I piece sits one cell lower-left of the SRS true rotation center.
+fn center_offset (p: Piece, r: u8) -> (i32, i32) {
+    if p == Piece::I && r == 2 { (1, 1) } else { (0, 0) }
+}
+
+/// Converts a `Move` on `state` to its TBP representation.
+pub fn to_tbp_move (state: &State, mov: &Move) -> TbpMove {
+    let p = state.placed_piece(mov);
+    let kind = TbpPiece::from_piece(p).expect("move places no piece");
+    let (dx, dy) = center_offset(p, mov.r);
+    TbpMove {
+        location: PieceLocation {
+            kind,
+            orientation: match mov.r {
+                0 => Orientation::North,
+                1 => Orientation::East,
+                2 => Orientation::South,
+                _ => Orientation::West,
+            },
+            x: mov.x as i32 + dx,
+            y: FIELD_H - 1 - (mov.y as i32 + dy),
+        },
+        spin: if mov.tspin { Spin::Full } else { Spin::None },
+    }
+}
+
+/// Converts a TBP move played on `state` back to a locked `Move`.
+///
+/// Returns `None` if the piece is neither the current nor the hold piece, lies off the board or
+/// overlaps filled cells.
+pub fn from_tbp_move (state: &State, mov: &TbpMove) -> Option<Move> {
+    let p: Piece = mov.location.kind.into();
+    let r = mov.location.orientation as u8;
+    let (dx, dy) = center_offset(p, r);
+    let x = mov.location.x - dx;
+    let y = FIELD_H - 1 - mov.location.y - dy;
+    if !(0..FIELD_H).contains(&y) || !(0..state.field.width as i32).contains(&x) || state.pieces.is_empty() {
+        return None;
+    }
+    let hold = if state.pieces[0] == p {
+        false
+    } else if state.hold_piece() == p {
+        true
+    } else {
+        return None;
+    };
+    let m = Move {
+        x: x as i8,
+        y: y as i8,
+        r,
+        hold,
+        tspin: mov.spin != Spin::None,
+        lock: true,
+        ..Move::new()
+    };
+    if state.field.check_conflict(&mut [[0; field::FIELD_H]; 4], &m, &p) {
+        return None;
+    }
+    Some(m)
+}
+
+/// TBP bot session.
+///
+/// Holds the game state between messages and answers requests with `solve`.
+pub struct Tbp {
+    state: Option<State>,
+    config: Config,
+}
+
+impl Tbp {
+    pub fn new (config: Config) -> Self {
+        Self {
+            state: None,
+            config,
+        }
+    }
+
+    /// Processes a frontend message, returning the reply, if any.
+    pub fn handle (&mut self, msg: FrontendMessage) -> Option<BotMessage> {
+        match msg {
+            FrontendMessage::Rules {} => Some(BotMessage::Ready {}),
+            FrontendMessage::Start(start) => {
+                self.state = Some(start.to_state());
+                None
+            },
+            FrontendMessage::Stop {} => {
+                self.state = None;
+                None
+            },
+            FrontendMessage::NewPiece { piece } => {
+                if let Some(state) = &mut self.state {
+                    state.push_piece(piece.into());
+                }
+                None
+            },
+            FrontendMessage::Play { mov } => {
+                let Some(state) = &self.state else {
+                    return Some(BotMessage::Error { reason: "no_game".to_string() });
+                };
+                let Some(next) = from_tbp_move(state, &mov).and_then(|m| state.play(&m)) else {
+                    return Some(BotMessage::Error { reason: "invalid_move".to_string() });
+                };
+                self.state = Some(next);
+                None
+            },
+            FrontendMessage::Suggest {} => Some(self.suggest()),
+            FrontendMessage::Quit {} => None,
+        }
+    }
+
+    /// Suggests placements for the current state: `solve`'s choice first, then every other
+    /// placement as a fallback.
+    fn suggest (&self) -> BotMessage {
+        let Some(state) = &self.state else {
+            return BotMessage::Error { reason: "no_game".to_string() };
+        };
+        let start = std::time::Instant::now();
+
+        let config = self.config.fit(state);
+
+        let mut moves: Vec<TbpMove> = vec![];
+        let mut extra = String::new();
+        if let Some((_, mov, score)) = solve(state, &config) {
+            moves.push(to_tbp_move(state, &mov));
+            extra = format!("score: {}", score);
+        }
+        let placements = gen_moves(state);
+        let nodes = placements.len() as u64;
+        for mov in placements.values() {
+            let m = to_tbp_move(state, mov);
+            if !moves.contains(&m) {
+                moves.push(m);
+            }
+        }
+
+        let dt = start.elapsed().as_secs_f64();
+        BotMessage::Suggestion {
+            moves,
+            move_info: MoveInfo {
+                nodes,
+                nps: if dt > 0.0 { nodes as f64 / dt } else { 0.0 },
+                extra,
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvaluatorMode;
+
+    fn start_msg () -> FrontendMessage {
+        let mut board = vec![vec![None::<String>; 10]; 40];
+        board[0] = (0..10).map(|x| if x < 8 { Some("G".to_string()) } else { None }).collect();
+        let json = serde_json::json!({
+            "type": "start",
+            "hold": null,
+            "queue": ["T", "I", "O", "S", "Z"],
+            "combo": 2,
+            "back_to_back": true,
+            "board": board,
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn tbp_start_test () {
+        let FrontendMessage::Start(start) = start_msg() else { panic!("not a start message") };
+        let state = start.to_state();
+
+        assert_eq!(state.field.m[39], 0b0_0_1_1_1_1_1_1_1_1);
+        assert_eq!(state.pieces.len(), 5);
+        assert_eq!(state.pieces[0], Piece::T);
+        assert_eq!(state.hold, Piece::None);
+        assert_eq!(state.props.b2b, 1);
+        assert_eq!(state.props.combo, 2);
+    }
+
+    #[test]
+    fn tbp_session_test () {
+        let mut bot = Tbp::new(Config::new(1, EvaluatorMode::Norm));
+        assert!(matches!(bot.handle(serde_json::from_str(r#"{"type":"rules"}"#).unwrap()), Some(BotMessage::Ready {})));
+        assert!(bot.handle(start_msg()).is_none());
+
+        let Some(BotMessage::Suggestion { moves, .. }) = bot.handle(FrontendMessage::Suggest {}) else {
+            panic!("expected a suggestion");
+        };
+        assert!(!moves.is_empty());
+        println!("{}", serde_json::to_string(&moves[0]).unwrap());
+
+        // Moves round-trip through the TBP representation
+        let state = bot.state.clone().unwrap();
+        let mov = from_tbp_move(&state, &moves[0]).unwrap();
+        assert_eq!(to_tbp_move(&state, &mov), moves[0]);
+
+        // South I is centered like SRS: cells (x-2..=x+1, y)
+        let mut flat = State::new();
+        flat.pieces.extend([Piece::I, Piece::O]);
+        let south = Move { x: 4, y: 38, r: 2, lock: true, ..Move::new() };
+        let loc = to_tbp_move(&flat, &south).location;
+        assert_eq!((loc.x, loc.y), (5, 0));
+        assert_eq!(flat.field.apply_move(&south, &Piece::I, &Piece::O).unwrap().m[39], 0b0_0_0_1_1_1_1_0_0_0);
+
+        // Placements off the board or overlapping the stack are reported, leaving the state as is
+        let mut bad = moves[0].clone();
+        bad.location = PieceLocation { kind: TbpPiece::T, orientation: Orientation::North, x: 0, y: 5 };
+        assert!(from_tbp_move(&state, &bad).is_none());
+        assert!(matches!(bot.handle(FrontendMessage::Play { mov: bad.clone() }), Some(BotMessage::Error { .. })));
+        bad.location = PieceLocation { kind: TbpPiece::T, orientation: Orientation::North, x: 3, y: 0 };
+        assert!(matches!(bot.handle(FrontendMessage::Play { mov: bad }), Some(BotMessage::Error { .. })));
+        assert_eq!(bot.state.as_ref().unwrap().pieces, state.pieces);
+
+        let consumed = if mov.hold {2} else {1};
+        assert!(bot.handle(FrontendMessage::Play { mov: moves[0].clone() }).is_none());
+        assert!(bot.handle(FrontendMessage::NewPiece { piece: TbpPiece::L }).is_none());
+        assert_eq!(bot.state.as_ref().unwrap().pieces.len(), 5 - consumed + 1);
+
+        assert!(bot.handle(FrontendMessage::Stop {}).is_none());
+        assert!(matches!(bot.handle(FrontendMessage::Play { mov: moves[0].clone() }), Some(BotMessage::Error { .. })));
+    }
+}
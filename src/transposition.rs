@@ -0,0 +1,146 @@
+//! Module implementing the transposition table of the search.
+//!
+//! Different move orders often reach the same position. The table caches the searched score of
+//! a position by its key, so transposed lines are not searched again. It is shared by all rayon
+//! workers through `Config::tt`, and may be kept across solves.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use super::State;
+use super::evaluator::EvaluatorMode;
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    key: u64,
+    score: f32,
+    depth: u8,
+    generation: u8,
+    used: bool,
+}
+
+/// Fixed-size transposition table.
+///
+/// Each position hashes to one slot. A slot is replaced when it is empty, left over from a
+/// previous solve, or holds a shallower search than the new entry (depth-preferred).
+pub struct TranspositionTable {
+    slots: Box<[Mutex<Slot>]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Table of `capacity` slots, rounded up to a power of two.
+    pub fn new (capacity: usize) -> Self {
+        let capacity = capacity.max(1).next_power_of_two();
+        Self {
+            slots: (0..capacity).map(|_| Mutex::new(Slot::default())).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// Table using about `mb` megabytes.
+    pub fn with_megabytes (mb: usize) -> Self {
+        Self::new((mb << 20) / std::mem::size_of::<Mutex<Slot>>())
+    }
+
+    pub fn capacity (&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Key of a position: field, hold, remaining queue and every property the search inherits.
+    pub fn key (state: &State, mode: EvaluatorMode) -> u64 {
+        let p = &state.props;
+        let mut hasher = DefaultHasher::new();
+        state.field.hash(&mut hasher);
+        state.hold.hash(&mut hasher);
+        state.pieces.hash(&mut hasher);
+        // Past the queue, values depend on what the bag can deal
+        state.bag.hash(&mut hasher);
+        state.garbage.hash(&mut hasher);
+        (p.b2b, p.combo, p.top_out).hash(&mut hasher);
+        // Totals as children inherit them, see `State::clone_as_child()`.
+        let no_atk = if p.ds > 0 && p.atk == 0 {p.ds} else {0};
+        (p.sum_atk + p.atk, p.sum_ds + p.ds, p.sum_no_atk + no_atk).hash(&mut hasher);
+        mode.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn slot (&self, key: u64) -> &Mutex<Slot> {
+        &self.slots[key as usize & (self.slots.len() - 1)]
+    }
+
+    /// Score of a position searched at least `depth` deep.
+    pub fn probe (&self, key: u64, depth: u8) -> Option<f32> {
+        let slot = *self.slot(key).lock().unwrap();
+        if slot.used && slot.key == key && slot.depth >= depth {
+            Some(slot.score)
+        } else {
+            None
+        }
+    }
+
+    /// Stores the score of a position searched `depth` deep.
+    pub fn store (&self, key: u64, depth: u8, score: f32) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let mut slot = self.slot(key).lock().unwrap();
+        if !slot.used || slot.generation != generation || depth >= slot.depth {
+            *slot = Slot { key, score, depth, generation, used: true };
+        }
+    }
+
+    /// Marks entries of previous solves as replaceable. Called at the start of every solve.
+    pub fn new_search (&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear (&self) {
+        for slot in self.slots.iter() {
+            *slot.lock().unwrap() = Slot::default();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piece;
+
+    #[test]
+    fn transposition_table_test () {
+        let tt = TranspositionTable::new(1000);
+        assert_eq!(tt.capacity(), 1024);
+
+        let mut state = State::new();
+        state.pieces.extend([Piece::T, Piece::I, Piece::O]);
+        let key = TranspositionTable::key(&state, EvaluatorMode::Norm);
+        assert_ne!(key, TranspositionTable::key(&state, EvaluatorMode::DS));
+
+        // Positions reached through different lines share a key when the search inherits the same totals
+        let mut a = state.clone();
+        a.props.sum_atk = 2;
+        let mut b = state.clone();
+        b.props.atk = 2;
+        assert_eq!(TranspositionTable::key(&a, EvaluatorMode::Norm), TranspositionTable::key(&b, EvaluatorMode::Norm));
+        b.props.b2b = 1;
+        assert_ne!(TranspositionTable::key(&a, EvaluatorMode::Norm), TranspositionTable::key(&b, EvaluatorMode::Norm));
+        b.props.b2b = 0;
+        b.pieces.pop_back();
+        assert_ne!(TranspositionTable::key(&a, EvaluatorMode::Norm), TranspositionTable::key(&b, EvaluatorMode::Norm));
+
+        // Deeper entries are kept, shallower requests served
+        tt.store(key, 2, 5.0);
+        tt.store(key, 1, 4.0);
+        tt.store(key ^ 1024, 1, 3.0);
+        assert_eq!(tt.probe(key, 1), Some(5.0));
+        assert_eq!(tt.probe(key, 3), None);
+        assert_eq!(tt.probe(key ^ 1024, 0), None);
+        tt.new_search();
+        tt.store(key ^ 1024, 1, 3.0);
+        assert_eq!(tt.probe(key ^ 1024, 1), Some(3.0));
+        tt.clear();
+        assert_eq!(tt.probe(key ^ 1024, 0), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Module implementing the search tree kept between the moves of a game.
+//!
+//! The DFS of `solve()` walks a tree of `Node`s, generating the children of a node on its first
+//! expansion only. `solve()` discards the tree once done; a `SearchTree` keeps it, re-roots it on
+//! the move played and extends it as preview pieces arrive, so the next search skips the move
+//! generation of every position already expanded.
+
+use super::{State, Move, Piece};
+use super::config::Config;
+use super::solve::{self, Line};
+use super::stats::SearchStats;
+
+/// Position of the search tree.
+pub(crate) struct Node {
+    pub(crate) state: State,
+    /// Placements from `state` and the positions they lead to, generated on the first expansion.
+    pub(crate) children: Option<Vec<(Move, Node)>>,
+}
+
+impl Node {
+    pub(crate) fn new (state: State) -> Self {
+        Self {
+            state,
+            children: None,
+        }
+    }
+
+    fn size (&self) -> usize {
+        1 + self.children.iter().flatten().map(|(_, c)| c.size()).sum::<usize>()
+    }
+
+    /// Appends `piece` to the queue of the whole subtree.
+    fn push_piece (&mut self, piece: Piece) {
+        // The hold alternative of a node without hold is the second piece, so its moves change
+        if self.state.hold == Piece::None && self.state.pieces.len() < 2 {
+            self.children = None;
+        }
+        self.state.push_piece(piece);
+        for (_, child) in self.children.iter_mut().flatten() {
+            child.push_piece(piece);
+        }
+    }
+}
+
+/// Search tree persisting across moves.
+///
+/// Usage mirrors `Mcts`: `solve()` at the root, then `play_move()` and `add_next_piece()` as the
+/// game goes. Past the queue, chance nodes are searched afresh every time. Beam searches do not
+/// use the tree.
+pub struct SearchTree {
+    root: Node,
+}
+
+impl SearchTree {
+    pub fn new (state: State) -> Self {
+        Self {
+            root: Node::new(state),
+        }
+    }
+
+    /// State at the root of the tree.
+    pub fn state (&self) -> &State {
+        &self.root.state
+    }
+
+    /// Number of positions held by the tree.
+    pub fn size (&self) -> usize {
+        self.root.size()
+    }
+
+    /// `solve::solve_stats()` from the root, reusing and growing the tree.
+    pub fn solve (&mut self, configs: &Config) -> (Option<(Line, u8)>, SearchStats) {
+        solve::solve_node(&mut self.root, configs)
+    }
+
+    /// Re-roots the tree on the position reached by `mov`, keeping its subtree.
+    ///
+    /// Starts a new tree if that position was never generated. Returns false if `mov` cannot be played.
+    pub fn play_move (&mut self, mov: &Move) -> bool {
+        let Some(next) = self.root.state.play(mov) else {
+            return false;
+        };
+        let children = self.root.children.take().unwrap_or_default();
+        self.root = children.into_iter()
+            .map(|(_, child)| child)
+            .find(|c| c.state.same_position(&next))
+            .unwrap_or_else(|| Node::new(next));
+        true
+    }
+
+    /// Reveals the next piece of the queue.
+    pub fn add_next_piece (&mut self, piece: Piece) {
+        self.root.push_piece(piece);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvaluatorMode;
+    use crate::solve::solve_stats;
+
+    #[test]
+    fn search_tree_test () {
+        let state: State = "
+            queue: TIOSZ
+            ##........
+            ###.....##
+            ####...###
+        ".parse().unwrap();
+        let configs = Config::new(3, EvaluatorMode::Norm);
+        let mut tree = SearchTree::new(state.clone());
+
+        // A fresh tree searches like `solve()`
+        let (res, stats) = tree.solve(&configs);
+        let (line, _) = res.unwrap();
+        let (fresh, _) = solve_stats(&state, &configs);
+        assert_eq!(line[0].1, fresh.unwrap().0[0].1);
+        println!("size: {}, {:?}", tree.size(), stats);
+        assert!(stats.moves > 0);
+
+        // Searching again generates no moves
+        let (_, again) = tree.solve(&configs);
+        assert_eq!(again.moves, 0);
+        assert_eq!(again.nodes, stats.nodes);
+
+        // Play the move & reveal the next piece
+        assert!(tree.play_move(&line[0].1));
+        let size = tree.size();
+        assert!(size > 1);
+        tree.add_next_piece(Piece::J);
+        assert_eq!(tree.state().pieces.back(), Some(&Piece::J));
+        assert_eq!(tree.state().field, line[0].0.field);
+
+        // The reused tree matches a search from scratch, with fewer moves generated
+        let configs = Config::new(2, EvaluatorMode::Norm);
+        let (reused, reused_stats) = tree.solve(&configs);
+        let (fresh, fresh_stats) = solve_stats(tree.state(), &configs);
+        println!("reused: {:?}\nfresh:  {:?}", reused_stats, fresh_stats);
+        assert_eq!(reused.unwrap().0[0].1, fresh.unwrap().0[0].1);
+        assert_eq!(reused_stats.nodes, fresh_stats.nodes);
+        assert!(reused_stats.moves < fresh_stats.moves);
+
+        // Moves off the tree start anew
+        let mut tree = SearchTree::new(state.clone());
+        assert!(tree.play_move(&line[0].1));
+        assert_eq!(tree.size(), 1);
+        assert_eq!(tree.state().field, line[0].0.field);
+    }
+
+    #[test]
+    fn search_tree_variant_test () {
+        let state: State = "
+            queue: IOT
+            #########.
+            #########.
+        ".parse().unwrap();
+        let mut tree = SearchTree::new(state.clone());
+        tree.solve(&Config::new(1, EvaluatorMode::Norm));
+
+        // The other t-spin variant of a generated clear reaches another position, so it starts anew
+        let mov = tree.root.children.iter().flatten()
+            .find(|(_, c)| c.state.props.ds > 0)
+            .map(|(m, _)| Move { tspin: !m.tspin, ..m.clone() })
+            .unwrap();
+        let next = state.play(&mov).unwrap();
+        assert!(tree.play_move(&mov));
+        assert_eq!(tree.state().props, next.props);
+        assert_eq!(tree.size(), 1);
+    }
+}
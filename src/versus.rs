@@ -0,0 +1,261 @@
+//! Module isolating the bot-vs-bot versus match harness.
+//!
+//! Two simulated games are played side by side, one piece each per round. Both placements of a
+//! round are resolved at once, so neither player moves first. Attack is sent to the opponent as
+//! garbage, which first cancels the sender's own pending garbage. Pending garbage lives in the
+//! `State`, so the search sees it coming.
+
+use super::Props;
+use super::state::Garbage;
+use super::field::FIELD_H;
+use super::config::Config;
+use super::rng::Rng;
+use super::sim::{Game, Stats};
+
+/// Rules of a match.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    /// Placements a garbage batch waits before it can enter the board: 0 enters on the
+    /// receiver's next placement.
+    pub garbage_delay: u32,
+    /// Simulated speed of both players, used for per-time statistics.
+    pub pps: f32,
+    /// Placements per player before the match is declared a draw.
+    pub max_pieces: u32,
+}
+
+impl Rules {
+    pub fn new () -> Self {
+        Self {
+            garbage_delay: 0,
+            pps: 2.0,
+            max_pieces: 500,
+        }
+    }
+}
+impl Default for Rules {
+    fn default () -> Self {
+        Self::new()
+    }
+}
+
+/// One side of a match.
+pub struct Player {
+    pub game: Game,
+    pub config: Config,
+    /// Garbage lines cleared.
+    pub garbage_cleared: u32,
+    /// Garbage rows currently at the bottom of the board.
+    garbage_height: u32,
+}
+
+impl Player {
+    /// Pending garbage lines.
+    pub fn incoming (&self) -> u32 {
+        self.game.state.incoming() as u32
+    }
+
+    /// Tracks the garbage rows at the bottom of the board after a placement.
+    ///
+    /// The state itself cancels and receives garbage, see `State::clone_as_child()`.
+    fn receive (&mut self, props: &Props) {
+        // Garbage rows are at the bottom, so cleared rows below the stack top are garbage.
+        let floor = FIELD_H as u32 - self.garbage_height;
+        let cleared = (floor..FIELD_H as u32).filter(|y| props.clears & (1 << y) > 0).count() as u32;
+        self.garbage_cleared += cleared;
+        self.garbage_height -= cleared;
+        self.garbage_height = (self.garbage_height + props.garbage as u32).min(FIELD_H as u32);
+    }
+}
+
+/// Result of a finished match.
+#[derive(Clone, Debug)]
+pub struct MatchResult {
+    /// Index of the winning player, `None` on a draw.
+    pub winner: Option<usize>,
+    pub stats: [Stats; 2],
+    pub garbage_cleared: [u32; 2],
+    /// Simulated match length in seconds.
+    pub seconds: f32,
+}
+
+impl MatchResult {
+    /// Attack per minute of player `i`.
+    pub fn apm (&self, i: usize) -> f32 {
+        self.stats[i].attack as f32 * 60.0 / self.seconds.max(f32::EPSILON)
+    }
+
+    /// VS score of player `i`: attack and garbage cleared per second, times 100.
+    pub fn vs (&self, i: usize) -> f32 {
+        (self.stats[i].attack + self.garbage_cleared[i]) as f32 * 100.0 / self.seconds.max(f32::EPSILON)
+    }
+}
+
+/// Versus match between two bots.
+pub struct Match {
+    pub players: [Player; 2],
+    pub rules: Rules,
+    rng: Rng,
+}
+
+impl Match {
+    /// Both players are dealt the same piece sequence, from `seed`.
+    pub fn new (seed: u64, configs: [Config; 2], rules: Rules) -> Self {
+        let players = configs.map(|config| Player {
+            game: Game::new(seed),
+            config,
+            garbage_cleared: 0,
+            garbage_height: 0,
+        });
+        Self {
+            players,
+            rules,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Whether the match has ended.
+    pub fn over (&self) -> bool {
+        self.players.iter().any(|p| p.game.over)
+            || self.players.iter().all(|p| p.game.stats.pieces >= self.rules.max_pieces)
+    }
+
+    /// Plays a round: one placement per player.
+    ///
+    /// Both players place on the boards left by the previous round, then exchange the attack
+    /// they sent. A round where either player tops out sends no garbage, ending the match.
+    pub fn step (&mut self) {
+        let mut sent: [u8; 2] = [0; 2];
+        for (player, sent) in self.players.iter_mut().zip(sent.iter_mut()) {
+            let incoming = player.game.state.incoming();
+            if player.game.step(&player.config).is_none() {
+                continue;
+            }
+            // The attack went to cancelling pending garbage first
+            let props = player.game.state.props;
+            *sent = props.atk.saturating_sub(incoming);
+            player.receive(&props);
+        }
+        if self.players.iter().any(|p| p.game.over) {
+            return;
+        }
+
+        for (i, &atk) in sent.iter().enumerate() {
+            if atk > 0 {
+                let hole = self.rng.below(self.players[1 - i].game.state.field.width as usize) as u8;
+                self.players[1 - i].game.queue_garbage(Garbage {
+                    lines: atk,
+                    hole,
+                    delay: self.rules.garbage_delay.min(u8::MAX as u32) as u8,
+                });
+            }
+        }
+    }
+
+    /// Plays the match to the end.
+    pub fn run (&mut self) -> MatchResult {
+        while !self.over() {
+            self.step();
+        }
+        let winner = match (self.players[0].game.over, self.players[1].game.over) {
+            (false, true) => Some(0),
+            (true, false) => Some(1),
+            _ => None,
+        };
+        let pieces = self.players.iter().map(|p| p.game.stats.pieces).max().unwrap_or(0);
+        MatchResult {
+            winner,
+            stats: [self.players[0].game.stats.clone(), self.players[1].game.stats.clone()],
+            garbage_cleared: [self.players[0].garbage_cleared, self.players[1].garbage_cleared],
+            seconds: pieces as f32 / self.rules.pps,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvaluatorMode, Field};
+
+    #[test]
+    fn versus_garbage_test () {
+        let mut field = Field::new();
+        field.m[39] = 0b0_0_0_0_0_0_0_0_1_1;
+        assert!(!field.push_garbage(2, 9));
+        assert_eq!(field.m[37], 0b0_0_0_0_0_0_0_0_1_1);
+        assert_eq!(field.m[38], 0b0_1_1_1_1_1_1_1_1_1);
+        assert_eq!(field.m[39], 0b0_1_1_1_1_1_1_1_1_1);
+
+        field.m[0] = 1;
+        assert!(field.push_garbage(1, 0));
+    }
+
+    #[test]
+    fn versus_cancel_test () {
+        let mut versus = Match::new(0, [Config::new(0, EvaluatorMode::Norm), Config::new(0, EvaluatorMode::Norm)], Rules::new());
+        let player = &mut versus.players[0];
+        player.game.state.garbage.push_back(Garbage { lines: 2, hole: 3, delay: 1 });
+
+        // Garbage waits for its delay, then enters on a non-clearing placement
+        player.game.step(&player.config).unwrap();
+        let props = player.game.state.props;
+        player.receive(&props);
+        assert_eq!(player.incoming(), 2);
+        player.game.step(&player.config).unwrap();
+        let props = player.game.state.props;
+        player.receive(&props);
+        assert_eq!(player.incoming(), 0);
+        assert_eq!(props.garbage, 2);
+        assert_eq!(player.game.state.field.m[39], 0b1_1_1_1_1_1_0_1_1_1);
+
+        // Clearing a garbage row counts toward the VS score
+        let mut props = Props::new();
+        props.ds = 1;
+        props.clears = 1 << 39;
+        player.receive(&props);
+        assert_eq!(player.garbage_cleared, 1);
+    }
+
+    #[test]
+    fn versus_round_test () {
+        // Neither player moves first: identical players play identical rounds
+        let config = Config::new(0, EvaluatorMode::Norm);
+        let mut versus = Match::new(5, [config.clone(), config.clone()], Rules::new());
+        for _ in 0..10 {
+            versus.step();
+            let [a, b] = &versus.players;
+            assert_eq!(a.game.state.field, b.game.state.field);
+            assert_eq!(a.game.state.incoming(), b.game.state.incoming());
+        }
+
+        // A top-out ends the match within its round, without sending garbage
+        let mut versus = Match::new(5, [config.clone(), config], Rules::new());
+        versus.players[1].game.state.garbage.push_back(Garbage { lines: 40, hole: 0, delay: 0 });
+        let res = versus.run();
+        assert_eq!(res.winner, Some(0));
+        assert_eq!(res.stats.map(|s| s.pieces), [1, 1]);
+        assert_eq!(versus.players[0].incoming(), 0);
+    }
+
+    #[test]
+    fn versus_match_test () {
+        let rules = Rules { max_pieces: 40, ..Rules::new() };
+        let mut versus = Match::new(3, [Config::new(1, EvaluatorMode::Norm), Config::new(0, EvaluatorMode::DS)], rules);
+        let res = versus.run();
+        println!("{:?}", res);
+        println!("apm: {:.1} / {:.1}, vs: {:.1} / {:.1}", res.apm(0), res.apm(1), res.vs(0), res.vs(1));
+        assert!(res.stats.iter().all(|s| s.pieces <= 40));
+        assert!(res.winner.is_some() || res.stats.iter().all(|s| s.pieces == 40));
+
+        assert!(versus.players.iter().any(|p| p.game.replay.to_string().contains("garbage")));
+
+        // Replays of both players play back, received garbage included
+        for player in versus.players.iter() {
+            let replay: crate::replay::Replay = player.game.replay.to_string().parse().unwrap();
+            let states = replay.states().unwrap();
+            assert_eq!(states.last().unwrap().field, player.game.state.field);
+            assert_eq!(states.last().unwrap().garbage, player.game.state.garbage);
+        }
+    }
+}